@@ -1,16 +1,72 @@
+use std::{fmt::Write, path::Path};
+
+/// Directory of `netstat -r` samples, with one subdirectory per dialect
+const NETSTAT_CORPUS_DIR: &str = "netstat/";
+
+/// Emit a string constant for every sample file, named after its path
+/// relative to `sample-tables/` (e.g., `sample-table.txt` -> `SAMPLE_TABLE`).
+/// Each sample's relative path (without extension) and constant name are
+/// collected into `samples`.
+fn emit_samples(
+    dir: &Path,
+    prefix: &str,
+    out: &mut String,
+    samples: &mut Vec<(String, String)>,
+) -> Result<(), std::io::Error> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(std::fs::DirEntry::path);
+    for entry in entries {
+        let path = entry.path();
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .expect("UTF-8 sample file name");
+        let rel_path = format!("{prefix}{stem}");
+        let name = rel_path
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+        if path.is_dir() {
+            emit_samples(&path, &format!("{rel_path}/"), out, samples)?;
+        } else if path.extension().is_some_and(|ext| ext == "txt") {
+            let sample = std::fs::read_to_string(&path)?;
+            writeln!(out, "#[allow(dead_code)]\nconst {name}: &str = {sample:?};")
+                .expect("write to String");
+            samples.push((rel_path, name));
+        }
+    }
+    Ok(())
+}
+
 fn main() -> Result<(), std::io::Error> {
-    let sample_table = std::fs::read_to_string(concat!(
-        env!("CARGO_MANIFEST_DIR"),
-        "/sample-tables/sample-table.txt"
-    ))?;
+    let mut out = String::new();
+    let mut samples = vec![];
+    emit_samples(
+        Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/sample-tables")),
+        "",
+        &mut out,
+        &mut samples,
+    )?;
 
-    let sample_table = format!("const SAMPLE_TABLE: &str = {sample_table:?};\n");
+    // One test per netstat corpus sample, named after its path
+    let mut corpus = String::from("netstat_corpus! {\n");
+    for (rel_path, name) in &samples {
+        if let Some(sample) = rel_path.strip_prefix(NETSTAT_CORPUS_DIR) {
+            let test = name[NETSTAT_CORPUS_DIR.len()..].to_ascii_lowercase();
+            writeln!(corpus, "    {test} => ({sample:?}, {name}),").expect("write to String");
+        }
+    }
+    corpus.push_str("}\n");
 
     let out_dir = std::env::var("OUT_DIR").expect("env OUT_DIR");
-    std::fs::write(
-        format!("{out_dir}/sample_table.rs"),
-        sample_table.as_bytes(),
-    )?;
+    std::fs::write(format!("{out_dir}/sample_table.rs"), out.as_bytes())?;
+    std::fs::write(format!("{out_dir}/netstat_corpus.rs"), corpus.as_bytes())?;
 
     Ok(())
 }
@@ -0,0 +1,191 @@
+/// The columns of a `netstat -r` table, as laid out by its header line.
+///
+/// `netstat` pads each cell to its column's width, so a cell's position
+/// identifies its column even when other cells are blank.  Text columns
+/// (`Destination`, `Gateway`, `Netmask`, ...) are left-aligned under their
+/// header, and numeric and interface columns are right-aligned.  A cell wider
+/// than its column pushes the rest of the row to the right, so the parser
+/// tracks that shift as it goes.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Columns<'a> {
+    columns: Vec<Column<'a>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Column<'a> {
+    name: &'a str,
+    start: usize,
+    end: usize,
+    align: Align,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Align {
+    Left,
+    Right,
+}
+
+impl<'a> Columns<'a> {
+    /// Lay out columns from a header line
+    pub(crate) fn new(header: &'a str) -> Self {
+        let columns = words(header)
+            .map(|(start, name)| Column {
+                name,
+                start,
+                end: start + name.len(),
+                align: match name {
+                    "Destination" | "Gateway" | "Netmask" | "Flags" | "Iface" | "Interface"
+                    | "Label" => Align::Left,
+                    _ => Align::Right,
+                },
+            })
+            .collect();
+        Columns { columns }
+    }
+
+    /// Column names, in order
+    pub(crate) fn names(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.columns.iter().map(|column| column.name)
+    }
+
+    /// Width of a column, from the start of its header to the start of the
+    /// next one
+    pub(crate) fn width(&self, name: &str) -> Option<usize> {
+        let index = self.columns.iter().position(|column| column.name == name)?;
+        let next = self.columns.get(index + 1)?;
+        Some(next.start - self.columns[index].start)
+    }
+
+    /// Split a row into `(column name, cell)` pairs, omitting blank cells.
+    /// Rows that don't line up with the header are split on whitespace
+    /// instead, assigning cells to columns in order.
+    pub(crate) fn split<'l>(&self, line: &'l str) -> Vec<(&'a str, &'l str)> {
+        self.split_aligned(line)
+            .unwrap_or_else(|| self.names().zip(line.split_ascii_whitespace()).collect())
+    }
+
+    /// Split a row by cell positions, or `None` if a cell doesn't line up with
+    /// any column
+    fn split_aligned<'l>(&self, line: &'l str) -> Option<Vec<(&'a str, &'l str)>> {
+        let mut cells = vec![];
+        // How far overlong cells have pushed the row to the right
+        let mut shift = 0;
+        let mut next = 0;
+        for (start, cell) in words(line) {
+            let end = start + cell.len();
+            let column = self.columns.get(next)?;
+            // A cell in its expected position may skip over blank columns
+            let aligned = self.columns[next..]
+                .iter()
+                .position(|column| match column.align {
+                    Align::Left => start == column.start + shift,
+                    Align::Right => end == column.end + shift,
+                });
+            if let Some(skip) = aligned {
+                next += skip;
+            } else {
+                // Otherwise, it's been pushed right by an overlong cell, and
+                // pushes everything after it by as much
+                let pushed = match column.align {
+                    Align::Left => start.checked_sub(column.start + shift)?,
+                    Align::Right => end.checked_sub(column.end + shift)?,
+                };
+                shift += pushed;
+            }
+            cells.push((self.columns[next].name, cell));
+            next += 1;
+        }
+        Some(cells)
+    }
+}
+
+/// Whitespace-separated words with their byte offsets
+fn words(line: &str) -> impl Iterator<Item = (usize, &str)> {
+    line.split(|c: char| c.is_ascii_whitespace())
+        .scan(0, |offset, word| {
+            let start = *offset;
+            *offset += word.len() + 1;
+            Some((start, word))
+        })
+        .filter(|(_, word)| !word.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Columns;
+
+    include!(concat!(env!("OUT_DIR"), "/sample_table.rs"));
+
+    const HEADER: &str = "Destination        Gateway            Flags           Netif Expire";
+
+    #[test]
+    fn blank_cells() {
+        let columns = Columns::new(HEADER);
+        assert_eq!(
+            columns.split("10.8/16                               UCS             utun3      !"),
+            [
+                ("Destination", "10.8/16"),
+                ("Flags", "UCS"),
+                ("Netif", "utun3"),
+                ("Expire", "!")
+            ]
+        );
+    }
+
+    #[test]
+    fn overlong_cells() {
+        let columns = Columns::new(HEADER);
+        assert_eq!(
+            columns.split("192.168.100.200/32 a2:5b:c1:0:0:1     UHLWIir         bridge100   1187"),
+            [
+                ("Destination", "192.168.100.200/32"),
+                ("Gateway", "a2:5b:c1:0:0:1"),
+                ("Flags", "UHLWIir"),
+                ("Netif", "bridge100"),
+                ("Expire", "1187")
+            ]
+        );
+    }
+
+    #[test]
+    fn misaligned_fallback() {
+        let columns = Columns::new(HEADER);
+        assert_eq!(
+            columns.split("default 192.168.64.1 UGScg en0"),
+            [
+                ("Destination", "default"),
+                ("Gateway", "192.168.64.1"),
+                ("Flags", "UGScg"),
+                ("Netif", "en0")
+            ]
+        );
+    }
+
+    #[test]
+    fn fixtures_align() {
+        for table in [
+            SAMPLE_TABLE,
+            SAMPLE_TABLE_EXTENDED,
+            SAMPLE_TABLE_BLANK_CELLS,
+            SAMPLE_TABLE_NETMASK,
+            NETSTAT_LEGACY_10_13_HIGH_SIERRA,
+            NETSTAT_MODERN_11_BIG_SUR,
+            NETSTAT_EXTENDED_13_VENTURA,
+            NETSTAT_WIDE_14_SONOMA,
+            NETSTAT_MODERN_14_SONOMA_ALL,
+            NETSTAT_FREEBSD_13_2,
+            NETSTAT_FREEBSD_WIDE_12_4,
+            NETSTAT_FREEBSD_WIDE_14_0,
+        ] {
+            let mut lines = table.lines();
+            let mut columns = Columns::default();
+            while let Some(line) = lines.next() {
+                if line.starts_with("Internet") {
+                    columns = Columns::new(lines.next().unwrap());
+                } else if !line.is_empty() && !line.starts_with("Routing") {
+                    assert!(columns.split_aligned(line).is_some(), "{:?}", line);
+                }
+            }
+        }
+    }
+}
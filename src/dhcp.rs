@@ -0,0 +1,447 @@
+use crate::{Entity, RoutingFlag, RoutingTable};
+use cidr::{AnyIpCidr, Ipv4Cidr};
+use std::{
+    convert::TryFrom,
+    net::{IpAddr, Ipv4Addr},
+    process::ExitStatus,
+    string::FromUtf8Error,
+    time::Duration,
+};
+use tokio::process::Command;
+
+const IPCONFIG_PATH: &str = "/usr/sbin/ipconfig";
+
+/// A DHCP lease, as printed by `ipconfig getpacket <interface>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpLease {
+    /// Interface the lease was obtained on
+    pub interface: String,
+
+    /// The leased address
+    pub yiaddr: Ipv4Addr,
+
+    pub server_identifier: Option<Ipv4Addr>,
+
+    pub subnet_mask: Option<Ipv4Addr>,
+
+    /// Routers (option 3), most-preferred first
+    pub routers: Vec<Ipv4Addr>,
+
+    pub domain_name_servers: Vec<Ipv4Addr>,
+
+    pub domain_name: Option<String>,
+
+    pub lease_time: Option<Duration>,
+
+    /// Classless static routes (option 121)
+    pub classless_routes: Vec<ClasslessRoute>,
+}
+
+/// A single classless static route (RFC 3442)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClasslessRoute {
+    pub destination: Ipv4Cidr,
+    /// `0.0.0.0` for a destination reachable directly on the interface
+    pub router: Ipv4Addr,
+}
+
+/// A disagreement between a DHCP lease and the routing table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DhcpDisagreement {
+    /// The lease offers a default router, but the interface has no default
+    /// route
+    MissingDefaultRoute { lease: Ipv4Addr },
+    /// The interface's default routes go through other routers
+    DefaultRouter { table: Vec<IpAddr>, lease: Ipv4Addr },
+    /// The table has no connected route for the leased subnet
+    MissingSubnetRoute { subnet: Ipv4Cidr },
+    /// A classless static route from the lease isn't in the table
+    MissingClasslessRoute(ClasslessRoute),
+}
+
+/// Various errors
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to execute {IPCONFIG_PATH}: {0}")]
+    IpconfigExec(std::io::Error),
+    #[error("failed to get DHCP packet: {0}")]
+    IpconfigFail(ExitStatus),
+    #[error("ipconfig output not UTF-8")]
+    IpconfigUtf8(FromUtf8Error),
+    #[error("missing {0:?} in DHCP packet")]
+    MissingField(&'static str),
+    #[error("invalid {field} {value:?} in DHCP packet")]
+    BadValue { field: &'static str, value: String },
+}
+
+impl DhcpLease {
+    /// Query the DHCP packet for `interface` using `ipconfig getpacket`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `ipconfig` command fails to execute (including
+    /// when the interface has no lease), or returns unparseable output.
+    pub async fn load_from_ipconfig(interface: &str) -> Result<Self, Error> {
+        let output = Command::new(IPCONFIG_PATH)
+            .arg("getpacket")
+            .arg(interface)
+            .stdin(std::process::Stdio::null())
+            .output()
+            .await
+            .map_err(Error::IpconfigExec)?;
+        if !output.status.success() {
+            return Err(Error::IpconfigFail(output.status));
+        }
+        Self::parse(
+            interface,
+            &String::from_utf8(output.stdout).map_err(Error::IpconfigUtf8)?,
+        )
+    }
+
+    /// Parse the output of `ipconfig getpacket <interface>`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the packet has no `yiaddr`, or contains
+    /// unparseable values
+    pub fn parse(interface: &str, output: &str) -> Result<Self, Error> {
+        let mut yiaddr = None;
+        let mut lease = DhcpLease {
+            interface: interface.into(),
+            yiaddr: Ipv4Addr::UNSPECIFIED,
+            server_identifier: None,
+            subnet_mask: None,
+            routers: vec![],
+            domain_name_servers: vec![],
+            domain_name: None,
+            lease_time: None,
+            classless_routes: vec![],
+        };
+        let mut lines = output.lines().peekable();
+        while let Some(line) = lines.next() {
+            // Header fields, e.g., `yiaddr = 192.168.64.23`
+            if let Some((key, value)) = line.split_once(" = ") {
+                if key == "yiaddr" {
+                    yiaddr = Some(parse_value("yiaddr", value)?);
+                }
+                continue;
+            }
+            // Options, e.g., `router (ip_mult): {192.168.64.1}`
+            let Some((name, rest)) = line.split_once(" (") else {
+                continue;
+            };
+            let value = rest.split_once("):").map_or("", |(_, value)| value.trim());
+            match name {
+                "server_identifier" => {
+                    lease.server_identifier = Some(parse_value("server_identifier", value)?);
+                }
+                "subnet_mask" => lease.subnet_mask = Some(parse_value("subnet_mask", value)?),
+                "router" => lease.routers = parse_ip_mult("router", value)?,
+                "domain_name_server" => {
+                    lease.domain_name_servers = parse_ip_mult("domain_name_server", value)?;
+                }
+                "domain_name" => lease.domain_name = Some(value.into()),
+                "lease_time" => {
+                    lease.lease_time = Some(Duration::from_secs(parse_uint("lease_time", value)?));
+                }
+                "option_121" | "classless_static_route" => {
+                    // Opaque options are printed as a hex dump on the
+                    // following lines
+                    let mut bytes = vec![];
+                    while let Some(row) = lines.next_if(|row| is_hex_dump_row(row)) {
+                        bytes.extend(parse_hex_dump_row(row));
+                    }
+                    lease.classless_routes = parse_classless_routes(&bytes)?;
+                }
+                _ => (),
+            }
+        }
+        lease.yiaddr = yiaddr.ok_or(Error::MissingField("yiaddr"))?;
+        Ok(lease)
+    }
+
+    /// The leased subnet, if the lease has a valid subnet mask
+    #[must_use]
+    pub fn subnet(&self) -> Option<Ipv4Cidr> {
+        let mask = u32::from(self.subnet_mask?);
+        if mask.leading_ones() != mask.count_ones() {
+            return None;
+        }
+        let len = u8::try_from(mask.count_ones()).ok()?;
+        Ipv4Cidr::new(Ipv4Addr::from(u32::from(self.yiaddr) & mask), len).ok()
+    }
+
+    /// The router the client should use for its default route.  Per RFC 3442,
+    /// classless static routes take precedence over the router option.
+    #[must_use]
+    pub fn default_router(&self) -> Option<Ipv4Addr> {
+        if self.classless_routes.is_empty() {
+            self.routers.first().copied()
+        } else {
+            self.classless_routes
+                .iter()
+                .find(|route| route.destination.network_length() == 0)
+                .map(|route| route.router)
+        }
+    }
+
+    /// Compare the lease with `table`'s routes for the lease's interface:
+    /// its default routers, the connected route for the leased subnet, and
+    /// any classless static routes.  An empty result means they agree.
+    #[must_use]
+    pub fn disagreements(&self, table: &RoutingTable) -> Vec<DhcpDisagreement> {
+        let mut disagreements = vec![];
+        if let Some(lease) = self.default_router() {
+            match table.default_gateways_for_netif(&self.interface) {
+                None => disagreements.push(DhcpDisagreement::MissingDefaultRoute { lease }),
+                Some(gateways) if !gateways.contains(&IpAddr::V4(lease)) => {
+                    disagreements.push(DhcpDisagreement::DefaultRouter {
+                        table: gateways.clone(),
+                        lease,
+                    });
+                }
+                Some(_) => (),
+            }
+        }
+
+        let has_route = |dest: Ipv4Cidr, gateway: Option<Ipv4Addr>| {
+            table.routes().iter().any(|route| {
+                route.net_if == self.interface
+                    && route.dest.entity == Entity::Cidr(AnyIpCidr::V4(dest))
+                    && gateway.is_none_or(|gateway| {
+                        if gateway.is_unspecified() {
+                            // On-link, e.g., through `link#N`
+                            !route.flags.contains(&RoutingFlag::Gateway)
+                        } else {
+                            route.gateway.entity
+                                == Entity::Cidr(AnyIpCidr::new_host(IpAddr::V4(gateway)))
+                        }
+                    })
+            })
+        };
+        if let Some(subnet) = self.subnet() {
+            if !has_route(subnet, None) {
+                disagreements.push(DhcpDisagreement::MissingSubnetRoute { subnet });
+            }
+        }
+        for route in &self.classless_routes {
+            if route.destination.network_length() > 0
+                && !has_route(route.destination, Some(route.router))
+            {
+                disagreements.push(DhcpDisagreement::MissingClasslessRoute(*route));
+            }
+        }
+        disagreements
+    }
+}
+
+/// Decode option 121: a sequence of prefix length, the significant octets of
+/// the destination, and the router
+fn parse_classless_routes(bytes: &[u8]) -> Result<Vec<ClasslessRoute>, Error> {
+    let bad_value = || Error::BadValue {
+        field: "classless static routes",
+        value: bytes
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<Vec<_>>()
+            .join(" "),
+    };
+    let mut routes = vec![];
+    let mut rest = bytes;
+    while let Some((&len, tail)) = rest.split_first() {
+        if len > 32 {
+            return Err(bad_value());
+        }
+        let significant = usize::from(len).div_ceil(8);
+        if tail.len() < significant + 4 {
+            return Err(bad_value());
+        }
+        let mut destination = [0; 4];
+        destination[..significant].copy_from_slice(&tail[..significant]);
+        let gateway =
+            <[u8; 4]>::try_from(&tail[significant..significant + 4]).map_err(|_| bad_value())?;
+        routes.push(ClasslessRoute {
+            destination: Ipv4Cidr::new(destination.into(), len).map_err(|_| bad_value())?,
+            router: gateway.into(),
+        });
+        rest = &tail[significant + 4..];
+    }
+    Ok(routes)
+}
+
+/// Whether `row` looks like `0000  10 0a 14 c0 ...`
+fn is_hex_dump_row(row: &str) -> bool {
+    row.split_ascii_whitespace()
+        .next()
+        .is_some_and(|offset| offset.len() == 4 && offset.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// The bytes of a hex dump row, stopping at the ASCII column
+fn parse_hex_dump_row(row: &str) -> Vec<u8> {
+    row.split_ascii_whitespace()
+        .skip(1)
+        .take(16)
+        .map_while(|byte| {
+            if byte.len() == 2 {
+                u8::from_str_radix(byte, 16).ok()
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Parse an `ip_mult` value, e.g., `{192.168.64.1, 1.1.1.1}`
+fn parse_ip_mult(field: &'static str, value: &str) -> Result<Vec<Ipv4Addr>, Error> {
+    value
+        .trim_start_matches('{')
+        .trim_end_matches('}')
+        .split(',')
+        .map(str::trim)
+        .filter(|addr| !addr.is_empty())
+        .map(|addr| parse_value(field, addr))
+        .collect()
+}
+
+/// Parse an unsigned option value, printed in hex, e.g., `0x15180`
+fn parse_uint(field: &'static str, value: &str) -> Result<u64, Error> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).map_err(|_| Error::BadValue {
+            field,
+            value: value.into(),
+        }),
+        None => parse_value(field, value),
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(field: &'static str, value: &str) -> Result<T, Error> {
+    value.parse().map_err(|_| Error::BadValue {
+        field,
+        value: value.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClasslessRoute, DhcpDisagreement, DhcpLease, Error};
+    use crate::RoutingTable;
+    use std::time::Duration;
+
+    include!(concat!(env!("OUT_DIR"), "/sample_table.rs"));
+
+    #[test]
+    fn parse_lease() {
+        let lease = DhcpLease::parse("en0", IPCONFIG_GETPACKET).expect("parse getpacket");
+        assert_eq!(lease.yiaddr.to_string(), "192.168.64.23");
+        assert_eq!(
+            lease.server_identifier,
+            Some("192.168.64.1".parse().unwrap())
+        );
+        assert_eq!(lease.subnet().unwrap().to_string(), "192.168.64.0/24");
+        assert_eq!(
+            lease.routers,
+            ["192.168.64.1".parse::<std::net::Ipv4Addr>().unwrap()]
+        );
+        assert_eq!(lease.domain_name_servers.len(), 2);
+        assert_eq!(lease.domain_name.as_deref(), Some("example.com"));
+        assert_eq!(lease.lease_time, Some(Duration::from_hours(24)));
+        assert_eq!(
+            lease.classless_routes,
+            [
+                ClasslessRoute {
+                    destination: "10.20.0.0/16".parse().unwrap(),
+                    router: "192.168.64.254".parse().unwrap(),
+                },
+                ClasslessRoute {
+                    destination: "0.0.0.0/0".parse().unwrap(),
+                    router: "192.168.64.1".parse().unwrap(),
+                },
+                ClasslessRoute {
+                    destination: "169.254.0.0/16".parse().unwrap(),
+                    router: "0.0.0.0".parse().unwrap(),
+                },
+            ]
+        );
+        assert_eq!(
+            lease.default_router(),
+            Some("192.168.64.1".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn lease_disagreements() {
+        let rt = RoutingTable::from_netstat_output(SAMPLE_TABLE).unwrap();
+        let lease = DhcpLease::parse("en0", IPCONFIG_GETPACKET).unwrap();
+        assert_eq!(
+            lease.disagreements(&rt),
+            [DhcpDisagreement::MissingClasslessRoute(
+                lease.classless_routes[0]
+            )]
+        );
+
+        // On-link routes need a direct route, not one through a gateway
+        let input = SAMPLE_TABLE.replace(
+            "169.254            link#5             UCS  ",
+            "169.254            192.168.64.1       UGSc ",
+        );
+        let rt_via_gateway = RoutingTable::from_netstat_output(&input).unwrap();
+        assert_eq!(
+            lease.disagreements(&rt_via_gateway),
+            [
+                DhcpDisagreement::MissingClasslessRoute(lease.classless_routes[0]),
+                DhcpDisagreement::MissingClasslessRoute(lease.classless_routes[2]),
+            ]
+        );
+
+        // Without option 121, the router option applies
+        let packet = IPCONFIG_GETPACKET
+            .split("option_121")
+            .next()
+            .unwrap()
+            .replace("{192.168.64.1}", "{192.168.64.2}");
+        let lease = DhcpLease::parse("en0", &packet).unwrap();
+        assert_eq!(
+            lease.disagreements(&rt),
+            [DhcpDisagreement::DefaultRouter {
+                table: vec!["192.168.64.1".parse().unwrap()],
+                lease: "192.168.64.2".parse().unwrap(),
+            }]
+        );
+
+        let lease = DhcpLease::parse("en1", &packet).unwrap();
+        assert_eq!(
+            lease.disagreements(&rt),
+            [
+                DhcpDisagreement::MissingDefaultRoute {
+                    lease: "192.168.64.2".parse().unwrap()
+                },
+                DhcpDisagreement::MissingSubnetRoute {
+                    subnet: "192.168.64.0/24".parse().unwrap()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_lease() {
+        assert!(matches!(
+            DhcpLease::parse("en0", "op = BOOTREPLY\n"),
+            Err(Error::MissingField("yiaddr"))
+        ));
+        // Truncated route: /16 needs two destination octets and a router
+        assert!(matches!(
+            DhcpLease::parse(
+                "en0",
+                "yiaddr = 192.168.64.23\noption_121 (opaque): \n0000  10 0a 14 c0 a8\n"
+            ),
+            Err(Error::BadValue { .. })
+        ));
+        assert!(matches!(
+            DhcpLease::parse("en0", "yiaddr = 192.168.64.23\nlease_time (uint32): 0xzz\n"),
+            Err(Error::BadValue {
+                field: "lease_time",
+                ..
+            })
+        ));
+    }
+}
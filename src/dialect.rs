@@ -0,0 +1,150 @@
+use crate::{columns::Columns, Protocol};
+
+/// Width of the Destination column in regular (not `-W`) output
+const fn default_destination_width(platform: NetstatPlatform, proto: Protocol) -> usize {
+    match (platform, proto) {
+        (_, Protocol::V4) => 18,
+        (NetstatPlatform::Darwin, Protocol::V6) => 39,
+        (NetstatPlatform::FreeBsd | NetstatPlatform::DragonFly, Protocol::V6) => 33,
+        (NetstatPlatform::OpenBsd | NetstatPlatform::NetBsd, Protocol::V6) => 34,
+    }
+}
+
+/// The variant of `netstat -r` output a table was parsed from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct NetstatDialect {
+    /// The system whose `netstat` printed the table
+    pub platform: NetstatPlatform,
+
+    /// The set of columns
+    pub layout: NetstatLayout,
+
+    /// Wide (`-W`) output, with columns sized to fit their contents
+    pub wide: bool,
+
+    /// `-a` output, which includes protocol-cloned routes.  Nothing in the
+    /// output itself tells it apart, so this is only set for tables loaded
+    /// with [`NetstatOptions::all`](crate::NetstatOptions::all).
+    pub all: bool,
+}
+
+/// The set of columns in `netstat -r` output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum NetstatLayout {
+    /// Before Big Sur: `Refs` and `Use` on IPv4 routes.  DragonFly prints
+    /// them on every route.
+    Legacy,
+    /// Big Sur and later: Destination, Gateway, Flags, Netif and Expire
+    #[default]
+    Modern,
+    /// `-l` output: adds `Refs`, `Use` and `Mtu`.  OpenBSD and NetBSD always
+    /// print these, and OpenBSD adds `Prio`.
+    Extended,
+}
+
+/// The systems whose `netstat -r` output can be parsed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum NetstatPlatform {
+    /// macOS and other Darwin releases
+    #[default]
+    Darwin,
+    /// FreeBSD, whose wide (`-W`) output adds `Use` (or `Nhop#`) and `Mtu`
+    FreeBsd,
+    /// OpenBSD, which adds `Refs`, `Use`, `Mtu`, `Prio` and sometimes
+    /// `Label`
+    OpenBsd,
+    /// NetBSD, which adds `Refs`, `Use` and `Mtu`, and calls the interface
+    /// column `Interface`
+    NetBsd,
+    /// DragonFly BSD, which adds `Refs` and `Use`, and with `-W`, `Mtu`
+    DragonFly,
+}
+
+impl NetstatPlatform {
+    /// Where the platform installs `netstat`
+    #[must_use]
+    pub fn netstat_path(self) -> &'static str {
+        match self {
+            NetstatPlatform::Darwin => "/usr/sbin/netstat",
+            NetstatPlatform::FreeBsd
+            | NetstatPlatform::OpenBsd
+            | NetstatPlatform::NetBsd
+            | NetstatPlatform::DragonFly => "/usr/bin/netstat",
+        }
+    }
+
+    /// The platform this program was built for, or Darwin if its `netstat`
+    /// can't be parsed
+    #[must_use]
+    pub fn host() -> Self {
+        if cfg!(target_os = "freebsd") {
+            NetstatPlatform::FreeBsd
+        } else if cfg!(target_os = "openbsd") {
+            NetstatPlatform::OpenBsd
+        } else if cfg!(target_os = "netbsd") {
+            NetstatPlatform::NetBsd
+        } else if cfg!(target_os = "dragonfly") {
+            NetstatPlatform::DragonFly
+        } else {
+            NetstatPlatform::Darwin
+        }
+    }
+}
+
+impl NetstatDialect {
+    /// The default dialect of `platform`, before any output is seen
+    #[must_use]
+    pub fn for_platform(platform: NetstatPlatform) -> Self {
+        NetstatDialect {
+            platform,
+            ..NetstatDialect::default()
+        }
+    }
+
+    /// Update the dialect from a section's column headers
+    pub(crate) fn observe_columns(&mut self, proto: Protocol, columns: &Columns) {
+        let has = |column| columns.names().any(|name| name == column);
+        // FreeBSD and DragonFly print a narrower flags field than Darwin's,
+        // and narrower IPv6 destinations
+        let bsd_widths = |platform| {
+            columns.width("Flags").is_some_and(|width| width <= 10)
+                && (proto == Protocol::V4
+                    || columns.width("Destination")
+                        == Some(default_destination_width(platform, proto) + 1))
+        };
+        if has("Refs") && has("Use") && has("Netif") && bsd_widths(NetstatPlatform::DragonFly) {
+            // Unlike Darwin's legacy output, DragonFly's has `Refs` and `Use`
+            // in its IPv6 section too
+            self.platform = NetstatPlatform::DragonFly;
+        } else if has("Netif")
+            && !has("Refs")
+            && !has("Mtu")
+            && bsd_widths(NetstatPlatform::FreeBsd)
+        {
+            // FreeBSD's regular output has Darwin's columns
+            self.platform = NetstatPlatform::FreeBsd;
+        }
+        if has("Prio") || has("Iface") {
+            // Only OpenBSD reports route priorities
+            self.platform = NetstatPlatform::OpenBsd;
+            self.layout = NetstatLayout::Extended;
+        } else if has("Interface") {
+            self.platform = NetstatPlatform::NetBsd;
+            self.layout = NetstatLayout::Extended;
+        } else if has("Nhop#") || (has("Mtu") && has("Use") && !has("Refs")) {
+            // Only FreeBSD's wide output has `Mtu` without `Refs`
+            self.platform = NetstatPlatform::FreeBsd;
+            self.wide = true;
+        } else if has("Mtu") {
+            self.layout = NetstatLayout::Extended;
+        } else if self.layout == NetstatLayout::Modern && has("Refs") {
+            self.layout = NetstatLayout::Legacy;
+        }
+        if columns
+            .width("Destination")
+            .is_some_and(|width| width > default_destination_width(self.platform, proto) + 1)
+        {
+            self.wide = true;
+        }
+    }
+}
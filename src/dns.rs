@@ -0,0 +1,386 @@
+use crate::{
+    nwi::parse_reachability, route_entry::parse_destination, Destination, Entity, LookupOptions,
+    Reachability, RouteEntry, RoutingTable,
+};
+use std::{process::ExitStatus, string::FromUtf8Error};
+use tokio::process::Command;
+
+const SCUTIL_PATH: &str = "/usr/sbin/scutil";
+
+/// The DNS resolver configuration, as listed by `scutil --dns`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DnsConfiguration {
+    /// Resolvers used for unscoped queries, in listing order
+    pub resolvers: Vec<Resolver>,
+
+    /// Resolvers used for queries scoped to an interface
+    pub scoped: Vec<Resolver>,
+}
+
+/// A single `resolver #N` block
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Resolver {
+    /// The resolver's number within its section
+    pub number: u32,
+
+    /// Domain the resolver answers for.  `None` for the default resolver
+    pub domain: Option<String>,
+
+    pub search_domains: Vec<String>,
+
+    /// Nameserver addresses, with their zone for link-local addresses
+    pub nameservers: Vec<Destination>,
+
+    pub port: Option<u16>,
+
+    /// Index and name of the interface the resolver is bound to
+    pub if_index: Option<u32>,
+    pub if_name: Option<String>,
+
+    /// Resolver flags, e.g., `Scoped` or `Request A records`
+    pub flags: Vec<String>,
+
+    pub reach: Option<Reachability>,
+
+    /// Search order; lower values are tried first
+    pub order: Option<u32>,
+
+    pub timeout: Option<u32>,
+
+    /// Resolver options, e.g., `mdns`
+    pub options: Option<String>,
+}
+
+/// The route a resolver's nameserver traffic takes
+#[derive(Debug, Clone)]
+pub struct NameserverRoute<'a> {
+    pub resolver: &'a Resolver,
+
+    /// The resolver comes from the scoped-query section
+    pub scoped_query: bool,
+
+    pub nameserver: &'a Destination,
+
+    /// The routing table entry matching the nameserver, if any
+    pub route: Option<&'a RouteEntry>,
+}
+
+/// Various errors
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to execute {SCUTIL_PATH}: {0}")]
+    ScutilExec(std::io::Error),
+    #[error("failed to get DNS configuration: {0}")]
+    ScutilFail(ExitStatus),
+    #[error("scutil output not UTF-8")]
+    ScutilUtf8(FromUtf8Error),
+    #[error("resolver details found outside a resolver: {0:?}")]
+    DetailBeforeResolver(String),
+    #[error("parsing nameserver: {0}")]
+    Nameserver(#[from] crate::route_entry::Error),
+    #[error("invalid {field} {value:?} in DNS configuration")]
+    BadValue { field: &'static str, value: String },
+}
+
+impl DnsConfiguration {
+    /// Query the DNS configuration using `scutil --dns`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `scutil` command fails to execute, or returns
+    /// unparseable output.
+    pub async fn load_from_scutil() -> Result<Self, Error> {
+        let output = Command::new(SCUTIL_PATH)
+            .arg("--dns")
+            .stdin(std::process::Stdio::null())
+            .output()
+            .await
+            .map_err(Error::ScutilExec)?;
+        if !output.status.success() {
+            return Err(Error::ScutilFail(output.status));
+        }
+        Self::parse(&String::from_utf8(output.stdout).map_err(Error::ScutilUtf8)?)
+    }
+
+    /// Parse the output of `scutil --dns`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the output contains unparseable values
+    pub fn parse(output: &str) -> Result<Self, Error> {
+        let mut config = DnsConfiguration::default();
+        let mut scoped = false;
+        for line in output.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with("DNS configuration") {
+                scoped = line.contains("scoped");
+                continue;
+            }
+            let section = if scoped {
+                &mut config.scoped
+            } else {
+                &mut config.resolvers
+            };
+            if let Some(number) = line.strip_prefix("resolver #") {
+                section.push(Resolver {
+                    number: parse_value("resolver number", number)?,
+                    ..Resolver::default()
+                });
+                continue;
+            }
+            let resolver = section
+                .last_mut()
+                .ok_or_else(|| Error::DetailBeforeResolver(line.into()))?;
+            parse_detail(resolver, line)?;
+        }
+        Ok(config)
+    }
+}
+
+impl RoutingTable {
+    /// Look up the route to each nameserver of each resolver, unscoped
+    /// resolvers first.  Resolvers without nameservers, e.g., mDNS, are
+    /// skipped.  Queries of scoped resolvers are sent through the resolver's
+    /// interface, so only routes on that interface are considered for them.
+    pub fn nameserver_routes<'a>(
+        &'a self,
+        dns: &'a DnsConfiguration,
+    ) -> impl Iterator<Item = NameserverRoute<'a>> {
+        let resolvers = dns
+            .resolvers
+            .iter()
+            .map(|resolver| (resolver, false))
+            .chain(dns.scoped.iter().map(|resolver| (resolver, true)));
+        resolvers.flat_map(move |(resolver, scoped_query)| {
+            resolver.nameservers.iter().map(move |nameserver| {
+                let options = LookupOptions {
+                    interface: resolver.if_name.as_deref().filter(|_| scoped_query),
+                    ..LookupOptions::default()
+                };
+                let route = match &nameserver.entity {
+                    Entity::Cidr(cidr) => cidr
+                        .first_address()
+                        .and_then(|addr| self.find_route_entry_with(addr, &options)),
+                    _ => None,
+                };
+                NameserverRoute {
+                    resolver,
+                    scoped_query,
+                    nameserver,
+                    route,
+                }
+            })
+        })
+    }
+}
+
+impl NameserverRoute<'_> {
+    /// The interface the nameserver traffic leaves through: the nameserver's
+    /// zone if it has one, otherwise the matching route's interface
+    #[must_use]
+    pub fn egress_interface(&self) -> Option<&str> {
+        self.nameserver
+            .zone
+            .as_deref()
+            .or_else(|| self.route.map(|route| route.net_if.as_str()))
+    }
+
+    /// Whether the nameserver traffic leaves via the interface the resolver
+    /// is bound to.  `None` if the resolver isn't bound to an interface, or
+    /// there's no route to the nameserver.
+    #[must_use]
+    pub fn leaves_via_resolver_interface(&self) -> Option<bool> {
+        let if_name = self.resolver.if_name.as_deref()?;
+        self.egress_interface().map(|net_if| net_if == if_name)
+    }
+}
+
+/// Parse a `key : value` line into `resolver`
+fn parse_detail(resolver: &mut Resolver, line: &str) -> Result<(), Error> {
+    let Some((key, value)) = line.split_once(':') else {
+        return Ok(());
+    };
+    let (key, value) = (key.trim(), value.trim());
+    match key {
+        "domain" => resolver.domain = Some(value.into()),
+        "port" => resolver.port = Some(parse_value("port", value)?),
+        "if_index" => {
+            // e.g., `5 (en0)`
+            let (index, name) = value.split_once(' ').unwrap_or((value, ""));
+            resolver.if_index = Some(parse_value("if_index", index)?);
+            let name = name.trim().trim_start_matches('(').trim_end_matches(')');
+            if !name.is_empty() {
+                resolver.if_name = Some(name.into());
+            }
+        }
+        "flags" => {
+            resolver.flags = value
+                .split(',')
+                .map(str::trim)
+                .filter(|flag| !flag.is_empty())
+                .map(ToOwned::to_owned)
+                .collect();
+        }
+        "reach" => {
+            resolver.reach = Some(parse_reachability(value).map_err(|_| Error::BadValue {
+                field: "reach",
+                value: value.into(),
+            })?);
+        }
+        "order" => resolver.order = Some(parse_value("order", value)?),
+        "timeout" => resolver.timeout = Some(parse_value("timeout", value)?),
+        "options" => resolver.options = Some(value.into()),
+        key if key.starts_with("search domain") => resolver.search_domains.push(value.into()),
+        key if key.starts_with("nameserver") => {
+            resolver.nameservers.push(parse_destination(value)?);
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+fn parse_value<T: std::str::FromStr>(field: &'static str, value: &str) -> Result<T, Error> {
+    value.parse().map_err(|_| Error::BadValue {
+        field,
+        value: value.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DnsConfiguration, Error};
+    use crate::RoutingTable;
+
+    include!(concat!(env!("OUT_DIR"), "/sample_table.rs"));
+
+    #[test]
+    fn parse_dns() {
+        let dns = DnsConfiguration::parse(SCUTIL_DNS).expect("parse scutil --dns");
+        assert_eq!(dns.resolvers.len(), 4);
+        assert_eq!(dns.scoped.len(), 2);
+
+        let default = &dns.resolvers[0];
+        assert_eq!(default.number, 1);
+        assert!(default.domain.is_none());
+        assert_eq!(default.search_domains, ["example.com", "lab.example.com"]);
+        assert_eq!(default.nameservers[1].to_string(), "2001:db8:64::1");
+        assert_eq!(default.if_index, Some(5));
+        assert_eq!(default.if_name.as_deref(), Some("en0"));
+        assert_eq!(default.flags, ["Request A records", "Request AAAA records"]);
+        assert_eq!(default.reach.as_ref().unwrap().flags, 0x0002_0002);
+
+        let corp = &dns.resolvers[1];
+        assert_eq!(corp.domain.as_deref(), Some("corp.example"));
+        assert_eq!(corp.order, Some(102_200));
+
+        let mdns = &dns.resolvers[3];
+        assert!(mdns.nameservers.is_empty());
+        assert_eq!(mdns.port, Some(5353));
+        assert_eq!(mdns.options.as_deref(), Some("mdns"));
+        assert_eq!(mdns.timeout, Some(5));
+
+        let scoped = &dns.scoped[0];
+        assert_eq!(scoped.flags[0], "Scoped");
+        assert_eq!(scoped.nameservers[1].zone.as_deref(), Some("en0"));
+    }
+
+    #[test]
+    fn nameserver_routes() {
+        let dns = DnsConfiguration::parse(SCUTIL_DNS).unwrap();
+        let rt = RoutingTable::from_netstat_output(SAMPLE_TABLE).unwrap();
+        let report: Vec<_> = rt
+            .nameserver_routes(&dns)
+            .map(|ns| {
+                (
+                    ns.scoped_query,
+                    ns.nameserver.to_string(),
+                    ns.egress_interface().map(ToOwned::to_owned),
+                    ns.leaves_via_resolver_interface(),
+                )
+            })
+            .collect();
+        let en0 = Some("en0".to_owned());
+        assert_eq!(
+            report,
+            [
+                (false, "192.168.64.1".into(), en0.clone(), Some(true)),
+                // IPv6 defaults go through the tunnels, not en0
+                (
+                    false,
+                    "2001:db8:64::1".into(),
+                    Some("utun0".into()),
+                    Some(false)
+                ),
+                // The VPN's nameserver isn't routed through the VPN
+                (false, "10.8.0.1".into(), en0.clone(), Some(false)),
+                (true, "192.168.64.1".into(), en0.clone(), Some(true)),
+                (
+                    true,
+                    "fe80::149d:99ff:fed7:7d64%en0".into(),
+                    en0.clone(),
+                    Some(true)
+                ),
+                // The scoped VPN resolver's queries only use routes on
+                // utun3, and there are none
+                (true, "10.8.0.1".into(), None, None),
+            ]
+        );
+
+        // Once the VPN routes its subnet, they leave via utun3
+        let input = SAMPLE_TABLE.replacen(
+            "127  ",
+            "10.8/16            10.8.0.5           UGSc            utun3       \n127  ",
+            1,
+        );
+        let rt = RoutingTable::from_netstat_output(&input).unwrap();
+        let vpn = rt
+            .nameserver_routes(&dns)
+            .filter(|ns| ns.resolver.if_name.as_deref() == Some("utun3"))
+            .map(|ns| (ns.scoped_query, ns.leaves_via_resolver_interface()))
+            .collect::<Vec<_>>();
+        assert_eq!(vpn, [(false, Some(true)), (true, Some(true))]);
+        let input = SAMPLE_TABLE.replacen(
+            "127  ",
+            "10.8.0.1           192.168.64.1       UGHSI             en0       \n\
+             10.8/16            10.8.0.5           UGScI           utun3       \n127  ",
+            1,
+        );
+        let rt = RoutingTable::from_netstat_output(&input).unwrap();
+        let vpn = rt
+            .nameserver_routes(&dns)
+            .filter(|ns| ns.resolver.if_name.as_deref() == Some("utun3"))
+            .map(|ns| {
+                (
+                    ns.scoped_query,
+                    ns.egress_interface().map(ToOwned::to_owned),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vpn,
+            [(false, Some("en0".into())), (true, Some("utun3".into()))]
+        );
+    }
+
+    #[test]
+    fn bad_dns() {
+        assert!(matches!(
+            DnsConfiguration::parse("DNS configuration\n  nameserver[0] : 192.168.64.1\n"),
+            Err(Error::DetailBeforeResolver(_))
+        ));
+        assert!(matches!(
+            DnsConfiguration::parse("resolver #1\n  if_index : five (en0)\n"),
+            Err(Error::BadValue {
+                field: "if_index",
+                ..
+            })
+        ));
+        assert!(matches!(
+            DnsConfiguration::parse("resolver #1\n  nameserver[0] : 192.168.64.1.5\n"),
+            Err(Error::Nameserver(_))
+        ));
+    }
+}
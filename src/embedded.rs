@@ -0,0 +1,215 @@
+use crate::{routing_table::NetstatParser, RoutingTable};
+use std::ops::Range;
+
+/// A routing table found inside a larger text, such as a log or support
+/// bundle
+#[derive(Debug)]
+pub struct EmbeddedTable {
+    pub table: RoutingTable,
+
+    /// Lines the table was parsed from, counting from zero
+    pub lines: Range<usize>,
+}
+
+impl RoutingTable {
+    /// Find and parse every `netstat -rn` listing embedded in `text`.
+    ///
+    /// A listing starts at a `Routing tables` (possibly naming a FIB),
+    /// `Internet:` or `Internet6:` line, and may be prefixed on every line by
+    /// a log prefix (e.g., a timestamp and process name).  The prefix is taken
+    /// from the first line of the listing, and later lines must have one of
+    /// the same shape, where numbers may differ, even in width.  The listing
+    /// ends at the first line that isn't part of it; trailing blank lines
+    /// aren't included in its range.
+    #[must_use]
+    pub fn scan_text(text: &str) -> Vec<EmbeddedTable> {
+        let lines = text.lines().collect::<Vec<_>>();
+        let mut tables = vec![];
+        let mut start = 0;
+        while start < lines.len() {
+            if let Some(found) = scan_table(&lines, start) {
+                start = found.lines.end;
+                tables.push(found);
+            } else {
+                start += 1;
+            }
+        }
+        tables
+    }
+}
+
+/// Parse the listing starting at `lines[start]`, if there is one
+fn scan_table(lines: &[&str], start: usize) -> Option<EmbeddedTable> {
+    let prefix = listing_prefix(lines[start])?;
+    let mut parser = NetstatParser::default();
+    let mut end = start;
+    let mut i = start;
+    while let Some(line) = lines.get(i).and_then(|line| strip_prefix(line, prefix)) {
+        match line.trim_end() {
+            "" => (),
+            title if i == start && title.starts_with("Routing tables") => parser.title(title),
+            section => {
+                if let Some(proto) = NetstatParser::section_proto(section) {
+                    // Next line will contain the column headers
+                    let Some(header) = lines
+                        .get(i + 1)
+                        .and_then(|header| strip_prefix(header, prefix))
+                        .filter(|header| header.starts_with("Destination"))
+                    else {
+                        break;
+                    };
+                    parser.section(proto, header);
+                    i += 1;
+                } else if parser.entry(line).is_err() {
+                    break;
+                }
+                end = i + 1;
+            }
+        }
+        i += 1;
+    }
+    if parser.is_empty() {
+        return None;
+    }
+    Some(EmbeddedTable {
+        table: parser.finish(),
+        lines: start..end,
+    })
+}
+
+/// The log prefix of a line starting a listing, or `None` if it doesn't start
+/// one
+fn listing_prefix(line: &str) -> Option<&str> {
+    let line = line.trim_end();
+    if let Some(at) = line.find("Routing tables") {
+        return Some(&line[..at]);
+    }
+    ["Internet:", "Internet6:"]
+        .iter()
+        .find_map(|marker| line.strip_suffix(marker))
+}
+
+/// Strip a log prefix of the same shape as `prefix` from a line.  Lines that
+/// are blank apart from the prefix strip to nothing.
+fn strip_prefix<'a>(line: &'a str, prefix: &str) -> Option<&'a str> {
+    let trimmed = line.trim_end();
+    if match_prefix(trimmed, prefix.trim_end()) == Some(trimmed.len()) {
+        return Some("");
+    }
+    line.get(match_prefix(line, prefix)?..)
+}
+
+/// Match `prefix` against the start of `line`, returning the length of the
+/// part of the line matched.  Each run of digits in the prefix, along with the
+/// spaces padding it, matches any such run in the line, so `diag[99]` matches
+/// `diag[100]` and `Oct  9` matches `Oct 10`.
+fn match_prefix(line: &str, prefix: &str) -> Option<usize> {
+    let (line, prefix) = (line.as_bytes(), prefix.as_bytes());
+    let spaces = |s: &[u8]| s.iter().take_while(|&&b| b == b' ').count();
+    let digits = |s: &[u8]| s.iter().take_while(|b| b.is_ascii_digit()).count();
+    let (mut i, mut j) = (0, 0);
+    while j < prefix.len() {
+        let padding = spaces(&prefix[j..]);
+        if prefix.get(j + padding).is_some_and(u8::is_ascii_digit) {
+            j += padding;
+            j += digits(&prefix[j..]);
+            i += spaces(&line[i..]);
+            let run = digits(&line[i..]);
+            if run == 0 {
+                return None;
+            }
+            i += run;
+        } else if line.get(i) == Some(&prefix[j]) {
+            i += 1;
+            j += 1;
+        } else {
+            return None;
+        }
+    }
+    Some(i)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Entity, RoutingTable};
+
+    include!(concat!(env!("OUT_DIR"), "/sample_table.rs"));
+
+    fn assert_same_routes(found: &RoutingTable, expected: &RoutingTable) {
+        let routes = |rt: &RoutingTable| {
+            rt.routes()
+                .iter()
+                .map(|route| (route.to_string(), route.flags.clone(), route.expires))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(routes(found), routes(expected));
+        assert_eq!(found.dialect(), expected.dialect());
+    }
+
+    #[test]
+    fn support_bundle() {
+        let tables = RoutingTable::scan_text(SUPPORT_BUNDLE);
+        assert_eq!(tables.len(), 2);
+
+        let plain = &tables[0];
+        assert_eq!(plain.lines, 6..28);
+        assert_same_routes(
+            &plain.table,
+            &RoutingTable::from_netstat_output(SAMPLE_TABLE_BLANK_CELLS).unwrap(),
+        );
+
+        // Every line carries a timestamp, down to the blank ones
+        let logged = &tables[1];
+        assert_eq!(logged.lines, 35..61);
+        assert_same_routes(
+            &logged.table,
+            &RoutingTable::from_netstat_output(NETSTAT_MODERN_11_BIG_SUR).unwrap(),
+        );
+        let entry = logged
+            .table
+            .find_route_entry("1.1.1.1".parse().unwrap())
+            .unwrap();
+        assert_eq!(entry.dest.entity, Entity::Default);
+    }
+
+    #[test]
+    fn log_prefix_widths() {
+        // The day and the process ID both gain a digit partway through
+        let lines = SAMPLE_TABLE.lines().collect::<Vec<_>>();
+        let (before, after) = lines.split_at(lines.len() / 2);
+        let text = before
+            .iter()
+            .map(|line| format!("Oct  9 23:59:59 diag[99]: {line}\n"))
+            .chain(
+                after
+                    .iter()
+                    .map(|line| format!("Oct 10 00:00:00 diag[100]: {line}\n")),
+            )
+            .collect::<String>();
+        let tables = RoutingTable::scan_text(&text);
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].lines, 0..lines.len());
+        assert_same_routes(
+            &tables[0].table,
+            &RoutingTable::from_netstat_output(SAMPLE_TABLE).unwrap(),
+        );
+    }
+
+    #[test]
+    fn whole_output() {
+        let tables = RoutingTable::scan_text(SAMPLE_TABLE);
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].lines, 0..SAMPLE_TABLE.lines().count());
+        assert_same_routes(
+            &tables[0].table,
+            &RoutingTable::from_netstat_output(SAMPLE_TABLE).unwrap(),
+        );
+    }
+
+    #[test]
+    fn no_tables() {
+        assert!(RoutingTable::scan_text("").is_empty());
+        assert!(RoutingTable::scan_text("Routing tables\n\nnothing to see\n").is_empty());
+        assert!(RoutingTable::scan_text("Internet:\nno header\n").is_empty());
+    }
+}
@@ -0,0 +1,425 @@
+use crate::{
+    route_entry::{parse_mac, parse_netmask},
+    Destination, Entity, LinkRef, RouteEntry, RoutingTable,
+};
+use mac_address::MacAddress;
+use std::{collections::HashSet, net::IpAddr, process::ExitStatus, string::FromUtf8Error};
+use tokio::process::Command;
+
+const IFCONFIG_PATH: &str = "/sbin/ifconfig";
+
+/// The network interfaces listed by `ifconfig -a`
+#[derive(Debug, Clone, Default)]
+pub struct InterfaceTable {
+    interfaces: Vec<Interface>,
+}
+
+/// A single network interface
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface {
+    /// BSD device name, e.g., `en0`
+    pub name: String,
+
+    /// Interface index, as used by `link#N` gateways and IPv6 scope IDs
+    pub index: Option<u32>,
+
+    /// Interface flags, e.g., `UP` or `LOOPBACK`
+    pub flags: HashSet<String>,
+
+    pub mtu: Option<u32>,
+
+    /// Hardware address
+    pub ether: Option<MacAddress>,
+
+    /// IPv4 addresses
+    pub inet: Vec<InterfaceAddr>,
+
+    /// IPv6 addresses
+    pub inet6: Vec<InterfaceAddr>,
+
+    /// Link status, e.g., `active` or `inactive`
+    pub status: Option<String>,
+
+    /// Media description, e.g., `autoselect`
+    pub media: Option<String>,
+}
+
+/// An address assigned to an interface
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceAddr {
+    pub addr: IpAddr,
+    pub prefix_len: Option<u8>,
+    /// IPv6 scope ID
+    pub scope_id: Option<u32>,
+    pub broadcast: Option<IpAddr>,
+    /// Remote end of a point-to-point link
+    pub peer: Option<IpAddr>,
+}
+
+/// Various errors
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to execute {IFCONFIG_PATH}: {0}")]
+    IfconfigExec(std::io::Error),
+    #[error("failed to list interfaces: {0}")]
+    IfconfigFail(ExitStatus),
+    #[error("ifconfig output not UTF-8")]
+    IfconfigUtf8(FromUtf8Error),
+    #[error("interface details found before interface name: {0:?}")]
+    DetailBeforeInterface(String),
+    #[error("invalid {field} {value:?} in ifconfig output")]
+    BadValue { field: &'static str, value: String },
+}
+
+impl InterfaceTable {
+    /// Query the interfaces using the `ifconfig` command.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `ifconfig` command fails to execute, or returns
+    /// unparseable output.
+    pub async fn load_from_ifconfig() -> Result<Self, Error> {
+        let output = Command::new(IFCONFIG_PATH)
+            .arg("-av")
+            .stdin(std::process::Stdio::null())
+            .output()
+            .await
+            .map_err(Error::IfconfigExec)?;
+        if !output.status.success() {
+            return Err(Error::IfconfigFail(output.status));
+        }
+        Self::parse(&String::from_utf8(output.stdout).map_err(Error::IfconfigUtf8)?)
+    }
+
+    /// Parse the output of `ifconfig -a` (or `ifconfig -av`).
+    ///
+    /// Interface indexes come from the `index` printed after the MTU with `-v`
+    /// when present, then from the scope ID of a link-local address.  Failing
+    /// both, the index is unknown.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the output contains unparseable values
+    pub fn parse(output: &str) -> Result<Self, Error> {
+        let mut interfaces: Vec<Interface> = vec![];
+        for line in output.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if !line.starts_with(char::is_whitespace) {
+                interfaces.push(parse_header(line)?);
+                continue;
+            }
+            let interface = interfaces
+                .last_mut()
+                .ok_or_else(|| Error::DetailBeforeInterface(line.into()))?;
+            parse_detail(interface, line.trim())?;
+        }
+
+        for interface in &mut interfaces {
+            if interface.index.is_none() {
+                interface.index = interface.inet6.iter().find_map(|addr| addr.scope_id);
+            }
+        }
+        Ok(InterfaceTable { interfaces })
+    }
+
+    /// All interfaces, in listing order
+    pub fn iter(&self) -> impl Iterator<Item = &Interface> {
+        self.interfaces.iter()
+    }
+
+    /// Find an interface by name
+    #[must_use]
+    pub fn by_name(&self, name: &str) -> Option<&Interface> {
+        self.interfaces
+            .iter()
+            .find(|interface| interface.name == name)
+    }
+
+    /// Find an interface by index
+    #[must_use]
+    pub fn by_index(&self, index: u32) -> Option<&Interface> {
+        self.interfaces
+            .iter()
+            .find(|interface| interface.index == Some(index))
+    }
+
+    /// Resolve a link entity (e.g., `link#N`) to the interface it refers to
+    #[must_use]
+    pub fn resolve_link(&self, entity: &Entity) -> Option<&Interface> {
+        match entity {
+            Entity::Link(LinkRef::Index(index)) => self.by_index(*index),
+            Entity::Link(LinkRef::Name(name)) => self.by_name(name),
+            _ => None,
+        }
+    }
+
+    /// Replace a numeric zone, such as a decoded embedded scope ID, with the
+    /// name of the interface with that index
+    pub fn resolve_zone(&self, dest: &mut Destination) {
+        let name = dest
+            .zone
+            .as_deref()
+            .and_then(|zone| zone.parse().ok())
+            .and_then(|index| self.by_index(index))
+            .map(|interface| interface.name.clone());
+        if name.is_some() {
+            dest.zone = name;
+        }
+    }
+}
+
+impl RoutingTable {
+    /// Pair each route with the interface it refers to.  Routes through a
+    /// `link#N` gateway resolve to that interface; all others resolve to their
+    /// `Netif`.
+    pub fn with_interfaces<'a>(
+        &'a self,
+        interfaces: &'a InterfaceTable,
+    ) -> impl Iterator<Item = (&'a RouteEntry, Option<&'a Interface>)> {
+        self.routes().iter().map(move |route| {
+            let interface = interfaces
+                .resolve_link(&route.gateway.entity)
+                .or_else(|| interfaces.by_name(&route.net_if));
+            (route, interface)
+        })
+    }
+
+    /// Copy the table with numeric zones in destinations and gateways resolved
+    /// to interface names, so they compare equal to zones given by name
+    #[must_use]
+    pub fn with_zone_names(&self, interfaces: &InterfaceTable) -> RoutingTable {
+        let routes = self
+            .routes()
+            .iter()
+            .cloned()
+            .map(|mut route| {
+                interfaces.resolve_zone(&mut route.dest);
+                interfaces.resolve_zone(&mut route.gateway);
+                route
+            })
+            .collect();
+        self.with_routes(routes)
+    }
+}
+
+/// Parse an interface's first line, e.g.,
+/// `en0: flags=8863<UP,BROADCAST,SMART,RUNNING,SIMPLEX,MULTICAST> mtu 1500`,
+/// which `-v` extends with `index 5`
+fn parse_header(line: &str) -> Result<Interface, Error> {
+    let (name, rest) = line
+        .split_once(": ")
+        .unwrap_or((line.trim_end_matches(':'), ""));
+    let flags = rest
+        .split_once('<')
+        .and_then(|(_, flags)| flags.split_once('>'))
+        .map(|(flags, _)| {
+            flags
+                .split(',')
+                .filter(|flag| !flag.is_empty())
+                .map(ToOwned::to_owned)
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut words = rest.split_ascii_whitespace();
+    let mut mtu = None;
+    let mut index = None;
+    while let Some(word) = words.next() {
+        match word {
+            "mtu" => mtu = words.next().map(|v| parse_value("mtu", v)).transpose()?,
+            "index" => index = words.next().map(|v| parse_value("index", v)).transpose()?,
+            _ => (),
+        }
+    }
+    Ok(Interface {
+        name: name.into(),
+        index,
+        flags,
+        mtu,
+        ether: None,
+        inet: vec![],
+        inet6: vec![],
+        status: None,
+        media: None,
+    })
+}
+
+/// Parse an indented detail line into `interface`
+fn parse_detail(interface: &mut Interface, line: &str) -> Result<(), Error> {
+    let (key, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim();
+    match key {
+        "ether" => {
+            interface.ether = Some(parse_mac(rest).map_err(|_| Error::BadValue {
+                field: "ether",
+                value: rest.into(),
+            })?);
+        }
+        "inet" => interface.inet.push(parse_addr(rest)?),
+        "inet6" => interface.inet6.push(parse_addr(rest)?),
+        "media:" => interface.media = Some(rest.into()),
+        "status:" => interface.status = Some(rest.into()),
+        _ => (),
+    }
+    Ok(())
+}
+
+/// Parse the remainder of an `inet` or `inet6` line, e.g.,
+/// `fe80::1%lo0 prefixlen 64 scopeid 0x1`
+fn parse_addr(rest: &str) -> Result<InterfaceAddr, Error> {
+    let mut words = rest.split_ascii_whitespace();
+    let addr_s = words.next().unwrap_or_default();
+    let addr = parse_value("address", addr_s.split('%').next().unwrap_or_default())?;
+    let mut addr = InterfaceAddr {
+        addr,
+        prefix_len: None,
+        scope_id: None,
+        broadcast: None,
+        peer: None,
+    };
+    while let Some(word) = words.next() {
+        let value = match word {
+            "netmask" | "prefixlen" | "scopeid" | "broadcast" | "-->" => {
+                words.next().unwrap_or_default()
+            }
+            // Bare attributes, e.g., `secured` or `autoconf`
+            _ => continue,
+        };
+        match word {
+            "netmask" => {
+                addr.prefix_len = Some(parse_netmask(value).map_err(|_| Error::BadValue {
+                    field: "netmask",
+                    value: value.into(),
+                })?);
+            }
+            "prefixlen" => addr.prefix_len = Some(parse_value("prefixlen", value)?),
+            "scopeid" => {
+                addr.scope_id = Some(
+                    u32::from_str_radix(value.trim_start_matches("0x"), 16).map_err(|_| {
+                        Error::BadValue {
+                            field: "scopeid",
+                            value: value.into(),
+                        }
+                    })?,
+                );
+            }
+            "broadcast" => addr.broadcast = Some(parse_value("broadcast", value)?),
+            _ => addr.peer = Some(parse_value("peer", value)?),
+        }
+    }
+    Ok(addr)
+}
+
+fn parse_value<T: std::str::FromStr>(field: &'static str, value: &str) -> Result<T, Error> {
+    value.parse().map_err(|_| Error::BadValue {
+        field,
+        value: value.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, InterfaceTable};
+    use crate::{route_entry::parse_destination, RoutingTable};
+
+    include!(concat!(env!("OUT_DIR"), "/sample_table.rs"));
+
+    #[test]
+    fn parse_ifconfig() {
+        let interfaces = InterfaceTable::parse(IFCONFIG_AV).expect("parse ifconfig");
+        assert_eq!(interfaces.iter().count(), 9);
+
+        let en0 = interfaces.by_name("en0").unwrap();
+        assert_eq!(en0.index, Some(5));
+        assert_eq!(en0.mtu, Some(1500));
+        assert!(en0.flags.contains("RUNNING"));
+        assert_eq!(en0.ether.unwrap().to_string(), "A4:83:E7:01:02:03");
+        assert_eq!(en0.inet[0].addr.to_string(), "192.168.64.23");
+        assert_eq!(en0.inet[0].prefix_len, Some(24));
+        assert_eq!(en0.inet6[0].scope_id, Some(5));
+        assert_eq!(en0.inet6[1].prefix_len, Some(64));
+        assert_eq!(en0.status.as_deref(), Some("active"));
+        assert_eq!(en0.media.as_deref(), Some("autoselect"));
+
+        // Indexes from the header, even without a link-local address
+        assert_eq!(interfaces.by_name("stf0").unwrap().index, Some(3));
+        let utun3 = interfaces.by_index(9).unwrap();
+        assert_eq!(utun3.name, "utun3");
+        assert_eq!(utun3.inet[0].peer, Some("10.8.0.5".parse().unwrap()));
+
+        // Without `-v`, only from link-local scope IDs, and never from
+        // position, as interfaces that were detached leave gaps
+        let plain = IFCONFIG_AV
+            .lines()
+            .map(|line| line.split(" index ").next().unwrap())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let interfaces = InterfaceTable::parse(&plain).unwrap();
+        assert_eq!(interfaces.by_name("en0").unwrap().index, Some(5));
+        assert_eq!(interfaces.by_name("stf0").unwrap().index, None);
+        assert_eq!(interfaces.by_name("utun3").unwrap().index, None);
+    }
+
+    #[test]
+    fn resolve_links() {
+        let interfaces = InterfaceTable::parse(IFCONFIG_AV).unwrap();
+        let rt = RoutingTable::from_netstat_output(SAMPLE_TABLE).unwrap();
+        for (route, interface) in rt.with_interfaces(&interfaces) {
+            let interface = interface.expect("every route has an interface");
+            if route.gateway.to_string() == "link#6" {
+                assert_eq!(interface.name, "utun0");
+                assert_eq!(route.net_if, "lo0");
+            } else if route.gateway.to_string() == "link#5" {
+                assert_eq!(interface.name, "en0");
+            }
+        }
+    }
+
+    #[test]
+    fn resolve_zones() {
+        let interfaces = InterfaceTable::parse(IFCONFIG_AV).unwrap();
+        let mut dest = parse_destination("fe80:5::1").unwrap();
+        assert_eq!(dest.zone.as_deref(), Some("5"));
+        interfaces.resolve_zone(&mut dest);
+        assert_eq!(dest, parse_destination("fe80::1%en0").unwrap());
+
+        // Unknown indexes and named zones are left alone
+        let mut dest = parse_destination("fe80:63::1").unwrap();
+        interfaces.resolve_zone(&mut dest);
+        assert_eq!(dest.zone.as_deref(), Some("99"));
+        let mut dest = parse_destination("fe80::1%utun0").unwrap();
+        interfaces.resolve_zone(&mut dest);
+        assert_eq!(dest.zone.as_deref(), Some("utun0"));
+
+        // Embedded scopes in the table resolve to the same zone as lo0's
+        let input = SAMPLE_TABLE.replace("fe80::1%lo0  ", "fe80:1::1    ");
+        let lo0 = parse_destination("fe80::1%lo0").unwrap();
+        let rt = RoutingTable::from_netstat_output(&input).unwrap();
+        let on_lo0 = |rt: &RoutingTable| {
+            rt.routes()
+                .iter()
+                .filter(|route| route.dest == lo0 || route.gateway == lo0)
+                .count()
+        };
+        assert_eq!(on_lo0(&rt), 0);
+        assert_eq!(on_lo0(&rt.with_zone_names(&interfaces)), 2);
+    }
+
+    #[test]
+    fn bad_ifconfig() {
+        assert!(matches!(
+            InterfaceTable::parse("\tinet 127.0.0.1\n"),
+            Err(Error::DetailBeforeInterface(_))
+        ));
+        assert!(matches!(
+            InterfaceTable::parse(
+                "en0: flags=0<> mtu 1500\n\tinet 192.168.64.23 netmask 0xff00ff00\n"
+            ),
+            Err(Error::BadValue {
+                field: "netmask",
+                ..
+            })
+        ));
+    }
+}
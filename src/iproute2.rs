@@ -0,0 +1,444 @@
+use crate::{
+    route_entry::parse_destination, Destination, Entity, LinkRef, Protocol, RouteEntry,
+    RoutingFlag, RoutingTable, RoutingTableSet,
+};
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+/// The interface Linux puts routes that discard traffic on, when none is given
+pub(crate) const DISCARD_INTERFACE: &str = "lo";
+
+/// IDs of the tables iproute2 names
+const TABLE_NAMES: &[(&str, u32)] = &[("default", 253), ("main", 254), ("local", 255)];
+
+/// The table routes are in when iproute2 doesn't name one
+const MAIN_TABLE_ID: u32 = 254;
+
+/// A route, as printed by `ip -j route show`
+#[derive(Debug, Deserialize)]
+struct JsonRoute {
+    #[serde(rename = "type")]
+    route_type: Option<String>,
+    dst: String,
+    gateway: Option<String>,
+    dev: Option<String>,
+    protocol: Option<String>,
+    scope: Option<String>,
+    metric: Option<u32>,
+    table: Option<String>,
+    expires: Option<u64>,
+    /// Router preference, which only IPv6 routes have
+    pref: Option<String>,
+    #[serde(default)]
+    flags: Vec<String>,
+    #[serde(default)]
+    nexthops: Vec<JsonNexthop>,
+}
+
+/// One of the paths of a multipath route
+#[derive(Debug, Deserialize)]
+struct JsonNexthop {
+    gateway: Option<String>,
+    dev: Option<String>,
+    #[serde(default)]
+    flags: Vec<String>,
+}
+
+/// Various errors
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("parsing iproute2 JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("parsing route to {dst:?}: {err}")]
+    Route {
+        dst: String,
+        err: crate::route_entry::Error,
+    },
+}
+
+impl RoutingTable {
+    /// Generate a `RoutingTable` from the JSON output of iproute2's
+    /// `ip -j route show` (or `ip -j -6 route show`).  Each path of a
+    /// multipath route becomes an entry of its own, flagged
+    /// [`RoutingFlag::Multipath`], and metrics become priorities.  If every
+    /// route is in the same table, its ID becomes the table ID.
+    ///
+    /// Lookups consider the routes of every table, so to keep the tables of
+    /// `ip -j route show table all` apart, use
+    /// [`RoutingTableSet::from_iproute2_json`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the JSON is malformed, or a route is unparseable
+    pub fn from_iproute2_json(json: &str) -> Result<RoutingTable, Error> {
+        Self::from_iproute2_json_with(json, &HashMap::new())
+    }
+
+    /// Generate a `RoutingTable` from the JSON output of iproute2's
+    /// `ip -j route show`, where tables may be named by `table_names` as
+    /// well as iproute2's built-in names, e.g., with the names given in
+    /// `/etc/iproute2/rt_tables`.  Routes in a table with an unknown name are
+    /// kept, but the table ID is then unknown.  See
+    /// [`RoutingTable::from_iproute2_json`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the JSON is malformed, or a route is unparseable
+    pub fn from_iproute2_json_with(
+        json: &str,
+        table_names: &HashMap<String, u32>,
+    ) -> Result<RoutingTable, Error> {
+        let mut table_ids = HashSet::new();
+        let mut routes = vec![];
+        for route in serde_json::from_str::<Vec<JsonRoute>>(json)? {
+            table_ids.insert(table_id(route.table.as_deref(), table_names));
+            routes.extend(route_entries(route)?);
+        }
+        let table = RoutingTable::from_routes(routes);
+        Ok(match table_ids.into_iter().collect::<Vec<_>>()[..] {
+            [Some(table_id)] => table.with_table_id(table_id),
+            _ => table,
+        })
+    }
+}
+
+impl RoutingTableSet {
+    /// Generate a set of tables from the JSON output of iproute2's
+    /// `ip -j route show table all`, keyed by Linux table ID (e.g., 254 for
+    /// `main`).  See [`RoutingTable::from_iproute2_json`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the JSON is malformed, or a route is unparseable
+    pub fn from_iproute2_json(json: &str) -> Result<RoutingTableSet, Error> {
+        Self::from_iproute2_json_with(json, &HashMap::new())
+    }
+
+    /// Generate a set of tables from the JSON output of iproute2's
+    /// `ip -j route show table all`, where tables may be named by
+    /// `table_names` as well as iproute2's built-in names.  The routes of a
+    /// table with an unknown name can't be keyed, so they're left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the JSON is malformed, or a route is unparseable
+    pub fn from_iproute2_json_with(
+        json: &str,
+        table_names: &HashMap<String, u32>,
+    ) -> Result<RoutingTableSet, Error> {
+        let mut by_table = std::collections::BTreeMap::<u32, Vec<RouteEntry>>::new();
+        for route in serde_json::from_str::<Vec<JsonRoute>>(json)? {
+            let Some(table_id) = table_id(route.table.as_deref(), table_names) else {
+                continue;
+            };
+            by_table
+                .entry(table_id)
+                .or_default()
+                .extend(route_entries(route)?);
+        }
+        Ok(by_table
+            .into_iter()
+            .map(|(table_id, routes)| RoutingTable::from_routes(routes).with_table_id(table_id))
+            .collect())
+    }
+}
+
+/// The ID of a table named by iproute2, which is `main` if not given, or
+/// `None` if the name is unknown
+fn table_id(table: Option<&str>, table_names: &HashMap<String, u32>) -> Option<u32> {
+    let Some(table) = table else {
+        return Some(MAIN_TABLE_ID);
+    };
+    TABLE_NAMES
+        .iter()
+        .find(|(name, _)| *name == table)
+        .map(|&(_, table_id)| table_id)
+        .or_else(|| table_names.get(table).copied())
+        .or_else(|| table.parse().ok())
+}
+
+/// The flags equivalent to a route's destination, protocol and type
+fn route_flags(
+    dest: &Destination,
+    protocol: Option<&str>,
+    route_type: Option<&str>,
+) -> HashSet<RoutingFlag> {
+    let mut flags = HashSet::new();
+    if let Entity::Cidr(cidr) = &dest.entity {
+        if cidr.is_host_address() {
+            flags.insert(RoutingFlag::Host);
+        }
+    }
+    match protocol {
+        // `boot` routes were added by hand, without naming a protocol
+        Some("static" | "boot") => {
+            flags.insert(RoutingFlag::Static);
+        }
+        Some("redirect") => {
+            flags.insert(RoutingFlag::Dynamic);
+        }
+        _ => (),
+    }
+    flags.extend(match route_type {
+        Some("unreachable" | "prohibit") => Some(RoutingFlag::Reject),
+        Some("blackhole") => Some(RoutingFlag::Blackhole),
+        Some("local") => Some(RoutingFlag::Local),
+        Some("broadcast") => Some(RoutingFlag::Broadcast),
+        Some("multicast") => Some(RoutingFlag::Multicast),
+        _ => None,
+    });
+    flags
+}
+
+/// Convert a route to one entry per path
+fn route_entries(route: JsonRoute) -> Result<Vec<RouteEntry>, Error> {
+    let JsonRoute {
+        route_type,
+        dst,
+        gateway,
+        dev,
+        protocol,
+        scope,
+        metric,
+        table: _,
+        expires,
+        pref,
+        flags: path_flags,
+        nexthops,
+    } = route;
+    let route_error = |err| Error::Route {
+        dst: dst.clone(),
+        err,
+    };
+    let dest = parse_destination(&dst).map_err(route_error)?;
+
+    let route_type = route_type.filter(|route_type| route_type != "unicast");
+    let mut flags = route_flags(&dest, protocol.as_deref(), route_type.as_deref());
+
+    // A single-path route is its own next hop
+    let nexthops = if nexthops.is_empty() {
+        vec![JsonNexthop {
+            gateway,
+            dev,
+            flags: path_flags,
+        }]
+    } else {
+        flags.insert(RoutingFlag::Multipath);
+        nexthops
+    };
+
+    let proto = if matches!(&dest.entity, Entity::Cidr(cidr) if cidr.is_ipv6())
+        || pref.is_some()
+        || nexthops.iter().any(|nexthop| {
+            nexthop
+                .gateway
+                .as_deref()
+                .is_some_and(|gw| gw.contains(':'))
+        }) {
+        Protocol::V6
+    } else {
+        Protocol::V4
+    };
+
+    nexthops
+        .into_iter()
+        .map(|nexthop| {
+            let mut flags = flags.clone();
+            // Paths whose link is down aren't used
+            if !nexthop
+                .flags
+                .iter()
+                .any(|flag| flag == "linkdown" || flag == "dead")
+            {
+                flags.insert(RoutingFlag::Up);
+            }
+            let net_if = match nexthop.dev {
+                Some(dev) => dev,
+                None if flags.contains(&RoutingFlag::Reject)
+                    || flags.contains(&RoutingFlag::Blackhole)
+                    || route_type.as_deref() == Some("throw") =>
+                {
+                    DISCARD_INTERFACE.into()
+                }
+                None => return Err(route_error(crate::route_entry::Error::MissingInterface)),
+            };
+            let gateway = match nexthop.gateway {
+                Some(gateway) => {
+                    flags.insert(RoutingFlag::Gateway);
+                    parse_destination(&gateway).map_err(route_error)?
+                }
+                None => Destination {
+                    entity: Entity::Link(LinkRef::Name(net_if.clone())),
+                    zone: None,
+                },
+            };
+            Ok(RouteEntry {
+                proto,
+                dest: dest.clone(),
+                gateway,
+                flags,
+                net_if,
+                expires: expires.map(Duration::from_secs),
+                refs: None,
+                use_count: None,
+                mtu: None,
+                // iproute2 omits a zero metric
+                priority: Some(metric.unwrap_or(0)),
+                label: None,
+                origin: protocol.clone(),
+                scope: scope.clone(),
+                route_type: route_type.clone(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Error;
+    use crate::{Entity, LinkRef, Protocol, RoutingFlag, RoutingTable, RoutingTableSet};
+    use std::time::Duration;
+
+    include!(concat!(env!("OUT_DIR"), "/sample_table.rs"));
+
+    #[test]
+    fn table_all() {
+        let set = RoutingTableSet::from_iproute2_json(IP_ROUTE_SHOW_TABLE_ALL).unwrap();
+        assert_eq!(
+            set.iter().map(|(id, _)| id).collect::<Vec<_>>(),
+            [100, 254, 255]
+        );
+        let main = set.get(254).unwrap();
+
+        // The lower metric wins
+        let entry = main.find_route_entry("1.1.1.1".parse().unwrap()).unwrap();
+        assert_eq!(entry.net_if, "eth0");
+        assert_eq!(entry.priority, Some(100));
+        assert_eq!(entry.origin.as_deref(), Some("dhcp"));
+        assert!(entry.flags.contains(&RoutingFlag::Gateway));
+        assert_eq!(main.default_gateways_for_netif("wlan0").unwrap().len(), 1);
+
+        let entries = main.find_multipath_entries("10.50.1.1".parse().unwrap());
+        assert_eq!(entries.len(), 2);
+        assert!(entries
+            .iter()
+            .all(|entry| entry.flags.contains(&RoutingFlag::Multipath)));
+        assert_eq!(entries[1].gateway.entity.to_string(), "192.168.1.3");
+
+        let entry = main.find_route_entry("10.8.0.77".parse().unwrap()).unwrap();
+        assert_eq!(entry.scope.as_deref(), Some("link"));
+        assert_eq!(
+            entry.gateway.entity,
+            Entity::Link(LinkRef::Name("wlan0".into()))
+        );
+        let entry = main
+            .find_route_entry("172.17.0.2".parse().unwrap())
+            .unwrap();
+        assert!(!entry.flags.contains(&RoutingFlag::Up));
+
+        let entry = main.find_route_entry("10.66.0.1".parse().unwrap()).unwrap();
+        assert!(entry.flags.contains(&RoutingFlag::Blackhole));
+        assert_eq!(entry.net_if, "lo");
+        for addr in ["10.98.0.1", "10.99.0.1"] {
+            let entry = main.find_route_entry(addr.parse().unwrap()).unwrap();
+            assert!(entry.flags.contains(&RoutingFlag::Reject), "{}", addr);
+        }
+
+        // Types without a flag are kept as they are
+        let policy = set.get(100).unwrap();
+        let entry = policy
+            .find_route_entry("10.1.1.1".parse().unwrap())
+            .unwrap();
+        assert_eq!(entry.route_type.as_deref(), Some("throw"));
+
+        let local = set.get(255).unwrap();
+        let entry = local
+            .find_route_entry("192.168.1.50".parse().unwrap())
+            .unwrap();
+        assert!(entry.flags.contains(&RoutingFlag::Local));
+        assert!(entry.flags.contains(&RoutingFlag::Host));
+        assert_eq!(entry.route_type.as_deref(), Some("local"));
+        let entry = local
+            .find_route_entry("192.168.1.255".parse().unwrap())
+            .unwrap();
+        assert!(entry.flags.contains(&RoutingFlag::Broadcast));
+    }
+
+    #[test]
+    fn ipv6() {
+        let rt = RoutingTable::from_iproute2_json(IP_6_ROUTE_SHOW_TABLE_ALL).unwrap();
+        assert!(rt.table_id().is_none());
+        assert!(rt.routes().iter().all(|route| route.proto == Protocol::V6));
+
+        let entry = rt
+            .find_route_entry("2606:4700::1111".parse().unwrap())
+            .unwrap();
+        assert_eq!(entry.expires, Some(Duration::from_secs(1772)));
+        assert_eq!(entry.gateway.entity.to_string(), "fe80::1");
+        let entry = rt.find_route_entry("fe80::1234".parse().unwrap()).unwrap();
+        assert_eq!(entry.net_if, "eth0");
+        let entry = rt
+            .find_route_entry("2001:db8:dead::1".parse().unwrap())
+            .unwrap();
+        assert!(entry.flags.contains(&RoutingFlag::Reject));
+    }
+
+    #[test]
+    fn main_table_only() {
+        let rt = RoutingTable::from_iproute2_json(
+            r#"[{"dst":"default","gateway":"10.0.0.1","dev":"eth0","flags":[]}]"#,
+        )
+        .unwrap();
+        assert_eq!(rt.table_id(), Some(254));
+        assert_eq!(rt.routes()[0].proto, Protocol::V4);
+    }
+
+    #[test]
+    fn missing_metric() {
+        // A route printed without a metric has metric 0, so it's preferred
+        let rt = RoutingTable::from_iproute2_json(
+            r#"[{"dst":"default","gateway":"10.0.0.1","dev":"eth1","metric":100,"flags":[]},
+                {"dst":"default","gateway":"10.0.0.2","dev":"eth0","flags":[]}]"#,
+        )
+        .unwrap();
+        let entry = rt.find_route_entry("1.1.1.1".parse().unwrap()).unwrap();
+        assert_eq!(entry.net_if, "eth0");
+        assert_eq!(entry.priority, Some(0));
+    }
+
+    #[test]
+    fn table_names() {
+        let vpn = r#"{"dst":"10.0.0.0/8","dev":"wg0","table":"vpn","flags":[]}"#;
+        let main = r#"{"dst":"default","gateway":"10.0.0.1","dev":"eth0","flags":[]}"#;
+        let json = format!("[{vpn},{main}]");
+        let names = vec![("vpn".to_owned(), 100)].into_iter().collect();
+
+        // Routes in tables with unknown names are kept, without a table ID
+        let rt = RoutingTable::from_iproute2_json(&format!("[{vpn}]")).unwrap();
+        assert_eq!(rt.table_id(), None);
+        assert_eq!(rt.routes()[0].net_if, "wg0");
+        let rt = RoutingTable::from_iproute2_json_with(&format!("[{vpn}]"), &names).unwrap();
+        assert_eq!(rt.table_id(), Some(100));
+
+        let set = RoutingTableSet::from_iproute2_json(&json).unwrap();
+        assert_eq!(set.iter().map(|(id, _)| id).collect::<Vec<_>>(), [254]);
+        let set = RoutingTableSet::from_iproute2_json_with(&json, &names).unwrap();
+        assert_eq!(set.iter().map(|(id, _)| id).collect::<Vec<_>>(), [100, 254]);
+        assert_eq!(set.get(100).unwrap().routes()[0].net_if, "wg0");
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(
+            RoutingTable::from_iproute2_json("{}"),
+            Err(Error::Json(_))
+        ));
+        assert!(matches!(
+            RoutingTable::from_iproute2_json(r#"[{"dst":"10.0.0.0/8"}]"#),
+            Err(Error::Route { dst, .. }) if dst == "10.0.0.0/8"
+        ));
+    }
+}
@@ -1,25 +1,59 @@
+mod columns;
+mod dhcp;
+mod dialect;
+mod dns;
+mod embedded;
+mod interface;
+mod iproute2;
+mod live_table;
+mod ndp;
+mod neighbor;
+mod nwi;
+mod procfs;
 mod route_entry;
+mod route_get;
+mod route_index;
+mod route_monitor;
 mod routing_flag;
 mod routing_table;
+mod services;
+#[cfg(feature = "sysdiagnose")]
+mod sysdiagnose;
+mod table_set;
 
 use std::fmt::Write;
 
-pub use routing_table::execute_netstat;
+pub use routing_table::{execute_netstat, execute_netstat_with};
 
 // Exports
+pub use dhcp::{ClasslessRoute, DhcpDisagreement, DhcpLease};
+pub use dialect::{NetstatDialect, NetstatLayout, NetstatPlatform};
+pub use dns::{DnsConfiguration, NameserverRoute, Resolver};
+pub use embedded::EmbeddedTable;
+pub use interface::{Interface, InterfaceAddr, InterfaceTable};
+pub use live_table::LiveRoutingTable;
+pub use ndp::{NdpEntry, NdpState, NdpTable};
+pub use neighbor::{Neighbor, NeighborTable};
+pub use nwi::{NetworkInterfaceOrder, NwiInterface, Reachability};
 pub use route_entry::RouteEntry;
+pub use route_get::{route_get, RouteGetDisagreement, RouteGetReport, RouteMetrics};
+pub use route_monitor::{parse_route_events, route_monitor, RouteEvent, RouteMessage, SockAddrs};
 pub use routing_flag::RoutingFlag;
-pub use routing_table::RoutingTable;
+pub use routing_table::{LookupOptions, NetstatOptions, RoutingTable};
+pub use services::{HardwarePort, NetworkService, NetworkServices};
+#[cfg(feature = "sysdiagnose")]
+pub use sysdiagnose::{MissingCapture, SysdiagnoseSnapshot};
+pub use table_set::RoutingTableSet;
 
 use cidr::AnyIpCidr;
 use mac_address::MacAddress;
 
 /// A generic network entity
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Entity {
     Default,
     Cidr(AnyIpCidr),
-    Link(String),
+    Link(LinkRef),
     Mac(MacAddress),
 }
 
@@ -28,7 +62,7 @@ impl std::fmt::Display for Entity {
         match self {
             Entity::Default => f.write_str("default"),
             Entity::Cidr(cidr) => write!(f, "{cidr}"),
-            Entity::Link(link) => f.write_str(link),
+            Entity::Link(link) => write!(f, "{link}"),
             Entity::Mac(mac) => {
                 for (i, byte) in mac.bytes().iter().enumerate() {
                     if i > 0 {
@@ -42,8 +76,37 @@ impl std::fmt::Display for Entity {
     }
 }
 
+/// A reference to the interface a route is directly on
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LinkRef {
+    /// By interface index, printed as `link#N`
+    Index(u32),
+    /// By interface name, as some releases print instead of `link#N`
+    Name(String),
+}
+
+impl LinkRef {
+    /// Interface index, if the link refers to one
+    #[must_use]
+    pub fn index(&self) -> Option<u32> {
+        match self {
+            LinkRef::Index(index) => Some(*index),
+            LinkRef::Name(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for LinkRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkRef::Index(index) => write!(f, "link#{index}"),
+            LinkRef::Name(name) => f.write_str(name),
+        }
+    }
+}
+
 /// A destination entity with an optional zone
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Destination {
     pub entity: crate::Entity,
     pub zone: Option<String>,
@@ -62,7 +125,7 @@ impl std::fmt::Display for Destination {
 }
 
 /// Internet Protocols associated with routing table entries
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Protocol {
     V4,
     V6,
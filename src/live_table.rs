@@ -0,0 +1,437 @@
+use crate::{
+    route_entry::{is_interface_name, parse_destination, with_netmask},
+    route_monitor::route_monitor,
+    Destination, Entity, LinkRef, Protocol, RouteEntry, RouteEvent, RouteMessage, RoutingFlag,
+    RoutingTable,
+};
+use cidr::AnyIpCidr;
+use futures::{Stream, StreamExt};
+use std::{
+    future::Future,
+    net::IpAddr,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+/// A routing table that is kept current by applying routing socket events
+///
+/// Clones share the same underlying table.  Readers take cheap, immutable
+/// snapshots, while a single task feeds events in with `apply` or `follow`.
+#[derive(Debug, Clone)]
+pub struct LiveRoutingTable {
+    current: Arc<RwLock<Arc<RoutingTable>>>,
+}
+
+/// Various errors
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("route monitor failed: {0}")]
+    Monitor(#[from] crate::route_monitor::Error),
+    #[error("re-syncing routing table: {0}")]
+    Resync(#[from] crate::routing_table::Error),
+    #[error("route message has no destination")]
+    MissingDestination,
+    #[error("parsing route message address: {0}")]
+    Address(#[from] crate::route_entry::Error),
+    #[error("can't determine the interface for route to {0}")]
+    UnknownInterface(Destination),
+    #[error("no route to {0} to update")]
+    NoMatchingRoute(Destination),
+}
+
+impl LiveRoutingTable {
+    /// Start tracking from an existing snapshot
+    #[must_use]
+    pub fn new(table: RoutingTable) -> Self {
+        LiveRoutingTable {
+            current: Arc::new(RwLock::new(Arc::new(table))),
+        }
+    }
+
+    /// Start tracking from the current output of `netstat`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the routing table can't be loaded
+    pub async fn load_from_netstat() -> Result<Self, Error> {
+        Ok(Self::new(RoutingTable::load_from_netstat().await?))
+    }
+
+    /// Take a snapshot of the table as it currently stands.  The snapshot
+    /// isn't affected by later updates.
+    #[must_use]
+    pub fn snapshot(&self) -> Arc<RoutingTable> {
+        Arc::clone(&self.current.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Replace the tracked table wholesale, e.g., after a re-sync
+    pub fn replace(&self, table: RoutingTable) {
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(table);
+    }
+
+    /// Apply a single event to the table, returning whether it changed.
+    /// Events that don't affect routes (interface status, lookups, failed
+    /// requests, ...) are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the event can't be applied cleanly, in which case
+    /// the table is left untouched and should be re-synced.
+    ///
+    /// Snapshots are immutable, so each change copies the routes and rebuilds
+    /// the lookup index: O(n) in the size of the table.
+    pub fn apply(&self, event: &RouteEvent) -> Result<bool, Error> {
+        // Hold the lock throughout, so concurrent updates can't be lost
+        let mut current = self.current.write().unwrap_or_else(PoisonError::into_inner);
+        let mut routes = current.routes().to_vec();
+        let changed = match event {
+            // A non-zero errno reports a request the kernel rejected
+            event if event.message().errno.unwrap_or(0) != 0 => false,
+            RouteEvent::Add(message) | RouteEvent::Resolve(message) => {
+                let route = route_from_message(message, &current)?;
+                if routes.iter().any(|r| same_route(r, &route)) {
+                    false
+                } else {
+                    routes.push(route);
+                    true
+                }
+            }
+            RouteEvent::Delete(message) => {
+                let (dest, gateway, net_if) = message_addrs(message)?;
+                let before = routes.len();
+                routes.retain(|r| {
+                    !is_target(r, message, &dest, net_if.as_deref())
+                        || gateway.as_ref().is_some_and(|gw| r.gateway != *gw)
+                });
+                if routes.len() == before {
+                    return Err(Error::NoMatchingRoute(dest));
+                }
+                true
+            }
+            RouteEvent::Change(message) => {
+                let (dest, gateway, net_if) = message_addrs(message)?;
+                // Only a scoped route is identified by its interface; an
+                // unscoped one may be moving to another
+                let scope = net_if
+                    .as_deref()
+                    .filter(|_| message.flags.contains(&RoutingFlag::IfScope));
+                let route = routes
+                    .iter_mut()
+                    .find(|r| is_target(r, message, &dest, scope))
+                    .ok_or(Error::NoMatchingRoute(dest))?;
+                if let Some(gateway) = gateway {
+                    route.gateway = gateway;
+                }
+                if let Some(net_if) = net_if {
+                    route.net_if = net_if;
+                }
+                route.flags.clone_from(&message.flags);
+                true
+            }
+            _ => false,
+        };
+        if changed {
+            *current = Arc::new(current.with_routes(routes));
+        }
+        Ok(changed)
+    }
+
+    /// Apply events from `events` until the stream ends.  Whenever an event
+    /// can't be parsed or applied cleanly, the table is rebuilt from scratch
+    /// with `resync`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a re-sync fails
+    pub async fn follow<S, F, Fut>(&self, events: S, mut resync: F) -> Result<(), Error>
+    where
+        S: Stream<Item = Result<RouteEvent, crate::route_monitor::Error>>,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<RoutingTable, crate::routing_table::Error>>,
+    {
+        futures::pin_mut!(events);
+        while let Some(event) = events.next().await {
+            let applied = match event {
+                Ok(event) => self.apply(&event).is_ok(),
+                Err(_) => false,
+            };
+            if !applied {
+                self.replace(resync().await?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Follow `route -n monitor`, re-syncing from `netstat` when needed.  This
+    /// runs until the monitor exits.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the monitor can't be started, or a re-sync fails
+    pub async fn follow_route_monitor(&self) -> Result<(), Error> {
+        self.follow(route_monitor()?, RoutingTable::load_from_netstat)
+            .await
+    }
+}
+
+/// Split a link-level address as printed by `route monitor` (e.g.,
+/// `en0:16.9d.99.d7.7d.65` or a bare `en0`) into the interface name and the
+/// optional hardware address.
+fn split_link(addr: &str) -> Option<(&str, Option<&str>)> {
+    let (name, lladdr) = match addr.split_once(':') {
+        Some((_, lladdr)) if lladdr.contains(':') => return None,
+        Some((name, lladdr)) => (name, Some(lladdr).filter(|a| !a.is_empty())),
+        None => (addr, None),
+    };
+    is_interface_name(name).then_some((name, lladdr))
+}
+
+/// Extract the destination, gateway and interface carried by a message
+fn message_addrs(
+    message: &RouteMessage,
+) -> Result<(Destination, Option<Destination>, Option<String>), Error> {
+    let addrs = &message.addrs;
+    let mut dest = with_netmask(
+        parse_destination(addrs.dst.as_deref().ok_or(Error::MissingDestination)?)?,
+        addrs.netmask.as_deref(),
+    )?;
+    if let Entity::Cidr(cidr) = dest.entity {
+        if cidr.network_length() == Some(0) {
+            dest.entity = Entity::Default;
+        }
+    }
+
+    let mut net_if = addrs
+        .ifp
+        .as_deref()
+        .and_then(split_link)
+        .map(|(name, _)| name.to_owned());
+    let gateway = match addrs.gateway.as_deref() {
+        None => None,
+        Some(gateway) if gateway.starts_with("link#") => Some(parse_destination(gateway)?),
+        Some(gateway) => match split_link(gateway) {
+            Some((name, lladdr)) => {
+                net_if.get_or_insert_with(|| name.to_owned());
+                match lladdr {
+                    Some(lladdr) => Some(parse_destination(lladdr)?),
+                    None => Some(Destination {
+                        entity: Entity::Link(LinkRef::Name(name.to_owned())),
+                        zone: None,
+                    }),
+                }
+            }
+            None => Some(parse_destination(gateway)?),
+        },
+    };
+    Ok((dest, gateway, net_if))
+}
+
+/// Build a route entry from an `RTM_ADD`/`RTM_RESOLVE` message.  When the
+/// message doesn't name an interface, use the one the table would route the
+/// gateway through, as the kernel does.
+fn route_from_message(message: &RouteMessage, table: &RoutingTable) -> Result<RouteEntry, Error> {
+    let (dest, gateway, net_if) = message_addrs(message)?;
+    let gateway = gateway.ok_or_else(|| Error::UnknownInterface(dest.clone()))?;
+    let net_if = match net_if {
+        Some(net_if) => net_if,
+        None => match &gateway.entity {
+            Entity::Cidr(cidr) => cidr
+                .first_address()
+                .and_then(|addr| table.find_route_entry(addr))
+                .map(|route| route.net_if.clone())
+                .ok_or_else(|| Error::UnknownInterface(dest.clone()))?,
+            _ => return Err(Error::UnknownInterface(dest)),
+        },
+    };
+    let proto = match &dest.entity {
+        Entity::Cidr(AnyIpCidr::V6(_)) => Protocol::V6,
+        Entity::Default => match &gateway.entity {
+            Entity::Cidr(cidr) if matches!(cidr.first_address(), Some(IpAddr::V6(_))) => {
+                Protocol::V6
+            }
+            _ => Protocol::V4,
+        },
+        _ => Protocol::V4,
+    };
+    Ok(RouteEntry {
+        proto,
+        dest,
+        gateway,
+        flags: message.flags.clone(),
+        net_if,
+        expires: None,
+        refs: None,
+        use_count: None,
+        mtu: None,
+        priority: None,
+        label: None,
+        origin: None,
+        scope: None,
+        route_type: None,
+    })
+}
+
+/// Whether a route is the one a `RTM_CHANGE`/`RTM_DELETE` message refers to.
+/// Scoped and unscoped routes to the same destination (e.g., a default route
+/// per interface) are told apart by the `IFSCOPE` flag and the interface.
+fn is_target(
+    route: &RouteEntry,
+    message: &RouteMessage,
+    dest: &Destination,
+    net_if: Option<&str>,
+) -> bool {
+    route.dest == *dest
+        && net_if.is_none_or(|net_if| route.net_if == net_if)
+        && route.flags.contains(&RoutingFlag::IfScope)
+            == message.flags.contains(&RoutingFlag::IfScope)
+}
+
+fn same_route(a: &RouteEntry, b: &RouteEntry) -> bool {
+    a.dest == b.dest && a.gateway == b.gateway && a.net_if == b.net_if
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LiveRoutingTable;
+    use crate::{parse_route_events, Entity, Protocol, RouteEvent, RoutingTable};
+    use futures::TryStreamExt;
+    use std::cell::Cell;
+
+    include!(concat!(env!("OUT_DIR"), "/sample_table.rs"));
+
+    async fn recorded_events() -> Vec<RouteEvent> {
+        parse_route_events(ROUTE_MONITOR.as_bytes())
+            .try_collect()
+            .await
+            .expect("parse route events")
+    }
+
+    #[tokio::test]
+    async fn apply_recorded_events() {
+        let live = LiveRoutingTable::new(RoutingTable::from_netstat_output(SAMPLE_TABLE).unwrap());
+        let before = live.snapshot();
+        let events = recorded_events().await;
+        let addr = "10.1.2.3".parse().unwrap();
+
+        // RTM_ADD of 10.1.0.0/16, with the interface inferred from the gateway
+        assert!(live.apply(&events[0]).unwrap());
+        let route = live.snapshot().find_route_entry(addr).cloned().unwrap();
+        assert_eq!(route.dest.to_string(), "10.1.0.0/16");
+        assert_eq!(route.gateway.to_string(), "192.168.64.1");
+        assert_eq!(route.net_if, "en0");
+        // Earlier snapshots are unaffected
+        assert!(matches!(
+            before.find_route_entry(addr).unwrap().dest.entity,
+            Entity::Default
+        ));
+
+        // RTM_RESOLVE of a neighbor
+        assert!(live.apply(&events[1]).unwrap());
+        let neighbor = live
+            .snapshot()
+            .find_route_entry("192.168.64.7".parse().unwrap())
+            .cloned();
+        assert!(matches!(neighbor.unwrap().gateway.entity, Entity::Mac(_)));
+
+        // RTM_CHANGE of the gateway
+        assert!(live.apply(&events[2]).unwrap());
+        let route = live.snapshot().find_route_entry(addr).cloned().unwrap();
+        assert_eq!(route.gateway.to_string(), "192.168.64.254");
+
+        // The rest delete what was added, around interface events that don't
+        // change anything.
+        for event in &events[3..] {
+            live.apply(event).unwrap();
+        }
+        assert_eq!(live.snapshot().routes().len(), before.routes().len());
+        assert!(matches!(
+            live.snapshot().find_route_entry(addr).unwrap().dest.entity,
+            Entity::Default
+        ));
+    }
+
+    #[tokio::test]
+    async fn scoped_routes() {
+        let live = LiveRoutingTable::new(RoutingTable::from_netstat_output(SAMPLE_TABLE).unwrap());
+        let input = "\
+RTM_ADD: Add Route: len 148, pid: 1, seq 1, errno 0, flags:<UP,GATEWAY,DONE,STATIC,IFSCOPE>
+sockaddrs: <DST,GATEWAY,NETMASK,IFP>
+ 0.0.0.0 192.168.65.1 0.0.0.0 en1:
+
+RTM_CHANGE: Change Metrics or flags: len 148, pid: 1, seq 2, errno 0, flags:<UP,GATEWAY,DONE,STATIC,IFSCOPE>
+sockaddrs: <DST,GATEWAY,NETMASK,IFP>
+ 0.0.0.0 192.168.65.254 0.0.0.0 en1:
+
+RTM_DELETE: Delete Route: len 148, pid: 1, seq 3, errno 0, flags:<GATEWAY,DONE,STATIC,IFSCOPE>
+sockaddrs: <DST,GATEWAY,NETMASK>
+ 0.0.0.0 192.168.65.254 0.0.0.0
+
+RTM_CHANGE: Change Metrics or flags: len 148, pid: 1, seq 4, errno 0, flags:<UP,GATEWAY,DONE,STATIC>
+sockaddrs: <DST,GATEWAY,NETMASK,IFP>
+ 0.0.0.0 192.168.65.1 0.0.0.0 en1:
+";
+        let events: Vec<RouteEvent> = parse_route_events(input.as_bytes())
+            .try_collect()
+            .await
+            .unwrap();
+        let default_gateways = |live: &LiveRoutingTable| {
+            let mut gateways: Vec<_> = live
+                .snapshot()
+                .routes()
+                .iter()
+                .filter(|r| r.dest.entity == Entity::Default && r.proto == Protocol::V4)
+                .map(|r| format!("{} {}", r.gateway, r.net_if))
+                .collect();
+            gateways.sort();
+            gateways
+        };
+
+        live.apply(&events[0]).unwrap();
+        // Changing the scoped default leaves the unscoped one alone
+        live.apply(&events[1]).unwrap();
+        assert_eq!(
+            default_gateways(&live),
+            ["192.168.64.1 en0", "192.168.65.254 en1"]
+        );
+        // So does deleting it, even without naming the interface
+        live.apply(&events[2]).unwrap();
+        assert_eq!(default_gateways(&live), ["192.168.64.1 en0"]);
+        // Changing the unscoped default can move it to another interface
+        live.apply(&events[3]).unwrap();
+        assert_eq!(default_gateways(&live), ["192.168.65.1 en1"]);
+    }
+
+    #[tokio::test]
+    async fn resync_on_unappliable_event() {
+        let live = LiveRoutingTable::new(RoutingTable::from_routes(vec![]));
+        let resyncs = Cell::new(0);
+        let resync = || {
+            resyncs.set(resyncs.get() + 1);
+            async { RoutingTable::from_netstat_output(SAMPLE_TABLE) }
+        };
+
+        // Deleting a route the table doesn't have forces a re-sync, after
+        // which the remaining events apply cleanly.
+        let events = recorded_events().await;
+        let delete = events[6].clone();
+        let script = futures::stream::iter(
+            std::iter::once(delete)
+                .chain(events.into_iter().take(3))
+                .map(Ok),
+        );
+        live.follow(script, resync).await.unwrap();
+        assert_eq!(resyncs.get(), 1);
+        let route = live
+            .snapshot()
+            .find_route_entry("10.1.2.3".parse().unwrap())
+            .cloned()
+            .unwrap();
+        assert_eq!(route.gateway.to_string(), "192.168.64.254");
+
+        // So does a stream error
+        let script = futures::stream::iter(vec![Err(
+            crate::route_monitor::Error::UnknownSockAddr("BOGUS".into()),
+        )]);
+        live.follow(script, resync).await.unwrap();
+        assert_eq!(resyncs.get(), 2);
+    }
+}
@@ -0,0 +1,364 @@
+use crate::{
+    route_entry::{parse_destination, parse_mac},
+    Destination, Entity, InterfaceTable, RouteEntry, RoutingTable,
+};
+use cidr::AnyIpCidr;
+use mac_address::MacAddress;
+use std::{collections::HashMap, process::ExitStatus, string::FromUtf8Error, time::Duration};
+use tokio::process::Command;
+
+const NDP_PATH: &str = "/usr/sbin/ndp";
+
+/// The IPv6 neighbor discovery cache, as listed by `ndp -an`
+#[derive(Debug, Clone, Default)]
+pub struct NdpTable {
+    entries: Vec<NdpEntry>,
+    /// Map of neighbors (address plus zone) to their position in `entries`
+    by_neighbor: HashMap<Destination, usize>,
+}
+
+/// A single neighbor cache entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NdpEntry {
+    /// Neighbor address, with its zone for link-local addresses
+    pub neighbor: Destination,
+
+    /// Link-layer address.  `None` while incomplete, or on links without one
+    pub lladdr: Option<MacAddress>,
+
+    /// Network interface the neighbor was seen on
+    pub net_if: String,
+
+    /// Time until the entry's state changes
+    pub expires: Option<Duration>,
+
+    /// The entry never expires
+    pub permanent: bool,
+
+    /// Neighbor unreachability detection state
+    pub state: NdpState,
+
+    /// The neighbor is a router
+    pub router: bool,
+
+    /// The entry is proxied
+    pub proxy: bool,
+
+    /// Number of unanswered probes sent
+    pub probes: u32,
+}
+
+/// Neighbor unreachability detection states
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NdpState {
+    NoState,    // N
+    Incomplete, // I
+    Reachable,  // R
+    Stale,      // S
+    Delay,      // D
+    Probe,      // P
+    WaitDelete, // W
+    Unknown,
+}
+
+impl From<char> for NdpState {
+    fn from(state_c: char) -> Self {
+        match state_c {
+            'N' => NdpState::NoState,
+            'I' => NdpState::Incomplete,
+            'R' => NdpState::Reachable,
+            'S' => NdpState::Stale,
+            'D' => NdpState::Delay,
+            'P' => NdpState::Probe,
+            'W' => NdpState::WaitDelete,
+            _ => NdpState::Unknown,
+        }
+    }
+}
+
+/// Various errors
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to execute {NDP_PATH}: {0}")]
+    NdpExec(std::io::Error),
+    #[error("failed to list neighbor cache: {0}")]
+    NdpFail(ExitStatus),
+    #[error("ndp output not UTF-8")]
+    NdpUtf8(FromUtf8Error),
+    #[error("incomplete neighbor cache entry {0:?}")]
+    ShortEntry(String),
+    #[error("parsing neighbor address: {0}")]
+    Neighbor(#[from] crate::route_entry::Error),
+    #[error("invalid {field} {value:?} in neighbor cache entry")]
+    BadValue { field: &'static str, value: String },
+}
+
+impl NdpTable {
+    /// Query the neighbor cache using the `ndp` command.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `ndp` command fails to execute, or returns
+    /// unparseable output.
+    pub async fn load_from_ndp() -> Result<Self, Error> {
+        let output = Command::new(NDP_PATH)
+            .arg("-an")
+            .stdin(std::process::Stdio::null())
+            .output()
+            .await
+            .map_err(Error::NdpExec)?;
+        if !output.status.success() {
+            return Err(Error::NdpFail(output.status));
+        }
+        Self::parse(&String::from_utf8(output.stdout).map_err(Error::NdpUtf8)?)
+    }
+
+    /// Parse the output of `ndp -an`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an entry is truncated or contains unparseable values
+    pub fn parse(output: &str) -> Result<Self, Error> {
+        let entries = output
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with("Neighbor"))
+            .map(parse_entry)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(NdpTable::from_entries(entries))
+    }
+
+    fn from_entries(entries: Vec<NdpEntry>) -> Self {
+        let by_neighbor = entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| (entry.neighbor.clone(), i))
+            .collect();
+        NdpTable {
+            entries,
+            by_neighbor,
+        }
+    }
+
+    /// Copy the table with numeric neighbor zones resolved to interface names,
+    /// to match routes resolved with [`RoutingTable::with_zone_names`]
+    #[must_use]
+    pub fn with_zone_names(&self, interfaces: &InterfaceTable) -> Self {
+        let entries = self
+            .entries
+            .iter()
+            .cloned()
+            .map(|mut entry| {
+                interfaces.resolve_zone(&mut entry.neighbor);
+                entry
+            })
+            .collect();
+        NdpTable::from_entries(entries)
+    }
+
+    /// All entries, in listing order
+    pub fn iter(&self) -> impl Iterator<Item = &NdpEntry> {
+        self.entries.iter()
+    }
+
+    /// Find the entry for a neighbor.  Link-local neighbors must carry the
+    /// same zone they're listed with, as in route gateways.
+    #[must_use]
+    pub fn get(&self, neighbor: &Destination) -> Option<&NdpEntry> {
+        self.by_neighbor.get(neighbor).map(|&i| &self.entries[i])
+    }
+}
+
+impl RoutingTable {
+    /// Pair each route that goes through an IPv6 gateway with the gateway's
+    /// neighbor cache entry.  Routes whose gateway isn't in the cache are
+    /// skipped.
+    pub fn gateway_ndp_entries<'a>(
+        &'a self,
+        ndp: &'a NdpTable,
+    ) -> impl Iterator<Item = (&'a RouteEntry, &'a NdpEntry)> {
+        self.routes()
+            .iter()
+            .filter_map(move |route| match route.gateway.entity {
+                Entity::Cidr(AnyIpCidr::V6(_)) => {
+                    ndp.get(&route.gateway).map(|entry| (route, entry))
+                }
+                _ => None,
+            })
+    }
+}
+
+/// Parse a single entry, e.g.,
+/// `fe80::149d:99ff:fed7:7d64%en0  16:9d:99:d7:7d:64  en0 8s  R R`
+fn parse_entry(line: &str) -> Result<NdpEntry, Error> {
+    let fields: Vec<&str> = line.split_ascii_whitespace().collect();
+    let [neighbor, lladdr, net_if, expire, state, rest @ ..] = &fields[..] else {
+        return Err(Error::ShortEntry(line.into()));
+    };
+
+    let lladdr = match *lladdr {
+        "(incomplete)" | "(none)" => None,
+        lladdr => Some(parse_mac(lladdr).map_err(|_| Error::BadValue {
+            field: "link-layer address",
+            value: lladdr.into(),
+        })?),
+    };
+    let (expires, permanent) = match *expire {
+        "permanent" => (None, true),
+        "expired" => (None, false),
+        expire => (Some(parse_duration(expire)?), false),
+    };
+
+    // Flags and probes are both optional; probes are numeric
+    let mut flags = "";
+    let mut probes = 0;
+    for field in rest {
+        if let Ok(n) = field.parse() {
+            probes = n;
+        } else {
+            flags = field;
+        }
+    }
+
+    Ok(NdpEntry {
+        neighbor: parse_destination(neighbor)?,
+        lladdr,
+        net_if: (*net_if).into(),
+        expires,
+        permanent,
+        state: state
+            .chars()
+            .next()
+            .map_or(NdpState::Unknown, NdpState::from),
+        router: flags.contains('R'),
+        proxy: flags.contains('p'),
+        probes,
+    })
+}
+
+/// Parse an expiration such as `23h59m58s` or `8s`
+fn parse_duration(s: &str) -> Result<Duration, Error> {
+    let bad_value = || Error::BadValue {
+        field: "expiration",
+        value: s.into(),
+    };
+    let mut secs: u64 = 0;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let split = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(bad_value)?;
+        let n: u64 = rest[..split].parse().map_err(|_| bad_value())?;
+        let unit = match rest[split..].chars().next() {
+            Some('d') => 86400,
+            Some('h') => 3600,
+            Some('m') => 60,
+            Some('s') => 1,
+            _ => return Err(bad_value()),
+        };
+        secs = n
+            .checked_mul(unit)
+            .and_then(|v| secs.checked_add(v))
+            .ok_or_else(bad_value)?;
+        rest = &rest[split + 1..];
+    }
+    Ok(Duration::from_secs(secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, NdpState, NdpTable};
+    use crate::{route_entry::parse_destination, InterfaceTable, RoutingTable};
+    use std::time::Duration;
+
+    include!(concat!(env!("OUT_DIR"), "/sample_table.rs"));
+
+    #[test]
+    fn parse_ndp() {
+        let ndp = NdpTable::parse(NDP_AN).expect("parse ndp");
+        assert_eq!(ndp.iter().count(), 10);
+
+        let router = ndp
+            .get(&parse_destination("fe80::149d:99ff:fed7:7d64%en0").unwrap())
+            .unwrap();
+        assert_eq!(router.lladdr.unwrap().to_string(), "16:9D:99:D7:7D:64");
+        assert_eq!(router.net_if, "en0");
+        assert_eq!(router.expires, Some(Duration::from_secs(8)));
+        assert_eq!(router.state, NdpState::Reachable);
+        assert!(router.router);
+
+        let global = ndp
+            .get(&parse_destination("2001:db8:64::1").unwrap())
+            .unwrap();
+        assert_eq!(global.expires, Some(Duration::from_secs(86398)));
+        assert_eq!(global.state, NdpState::Stale);
+
+        let incomplete = ndp
+            .get(&parse_destination("fe80::6e4:1aff:fe30:77b2%en0").unwrap())
+            .unwrap();
+        assert!(incomplete.lladdr.is_none());
+        assert_eq!(incomplete.state, NdpState::Incomplete);
+        assert_eq!(incomplete.probes, 3);
+        assert!(!incomplete.router);
+
+        // The zone is part of the key
+        assert!(ndp
+            .get(&parse_destination("fe80::1%en0").unwrap())
+            .is_none());
+        assert!(
+            ndp.get(&parse_destination("fe80::1%lo0").unwrap())
+                .unwrap()
+                .permanent
+        );
+    }
+
+    #[test]
+    fn gateway_entries() {
+        let ndp = NdpTable::parse(NDP_AN).unwrap();
+        let rt = RoutingTable::from_netstat_output(SAMPLE_TABLE).unwrap();
+        let gateways: Vec<String> = rt
+            .gateway_ndp_entries(&ndp)
+            .map(|(route, entry)| {
+                assert_eq!(route.gateway, entry.neighbor);
+                entry.neighbor.to_string()
+            })
+            .collect();
+        assert!(gateways.contains(&"fe80::21c1:53b6:e09d:8ea1%utun0".to_owned()));
+        assert!(gateways.contains(&"fe80::1%lo0".to_owned()));
+    }
+
+    #[test]
+    fn embedded_scope() {
+        let interfaces = InterfaceTable::parse(IFCONFIG_AV).unwrap();
+        let input = NDP_AN.replace("fe80::1%lo0 ", "fe80:1::1   ");
+        let ndp = NdpTable::parse(&input).unwrap();
+        let lo0 = parse_destination("fe80::1%lo0").unwrap();
+        assert!(ndp.get(&lo0).is_none());
+        assert!(ndp.get(&parse_destination("fe80::1%1").unwrap()).is_some());
+        assert!(
+            ndp.with_zone_names(&interfaces)
+                .get(&lo0)
+                .unwrap()
+                .permanent
+        );
+    }
+
+    #[test]
+    fn bad_ndp() {
+        assert!(matches!(
+            NdpTable::parse("fe80::1%lo0 (incomplete) lo0"),
+            Err(Error::ShortEntry(_))
+        ));
+        assert!(matches!(
+            NdpTable::parse("fe80::1%lo0 (incomplete) lo0 12q R"),
+            Err(Error::BadValue { .. })
+        ));
+        assert!(matches!(
+            NdpTable::parse("fe80::1%lo0 (incomplete) lo0 99999999999999999d R"),
+            Err(Error::BadValue { .. })
+        ));
+        assert!(matches!(
+            NdpTable::parse("fe80::1%lo0 (incomplete) lo0 18446744073709551615s1s R"),
+            Err(Error::BadValue { .. })
+        ));
+    }
+}
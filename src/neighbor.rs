@@ -0,0 +1,249 @@
+use crate::{route_entry::parse_mac, Entity, RouteEntry, RoutingTable};
+use cidr::AnyIpCidr;
+use mac_address::MacAddress;
+use std::{net::Ipv4Addr, process::ExitStatus, string::FromUtf8Error, time::Duration};
+use tokio::process::Command;
+
+const ARP_PATH: &str = "/usr/sbin/arp";
+
+/// The IPv4 neighbor (ARP) cache, as listed by `arp -an`
+#[derive(Debug, Clone, Default)]
+pub struct NeighborTable {
+    neighbors: Vec<Neighbor>,
+}
+
+/// A single ARP cache entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Neighbor {
+    pub addr: Ipv4Addr,
+
+    /// Hardware address.  `None` while resolution is incomplete
+    pub mac: Option<MacAddress>,
+
+    /// Network interface the neighbor was seen on
+    pub net_if: String,
+
+    /// Time until the entry expires
+    pub expires: Option<Duration>,
+
+    /// The entry never expires
+    pub permanent: bool,
+
+    /// The entry is scoped to its interface
+    pub ifscope: bool,
+
+    /// Link type, e.g., `ethernet`
+    pub link_type: Option<String>,
+}
+
+/// Various errors
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to execute {ARP_PATH}: {0}")]
+    ArpExec(std::io::Error),
+    #[error("failed to list ARP cache: {0}")]
+    ArpFail(ExitStatus),
+    #[error("arp output not UTF-8")]
+    ArpUtf8(FromUtf8Error),
+    #[error("unrecognized ARP entry {0:?}")]
+    BadEntry(String),
+    #[error("invalid {field} {value:?} in ARP entry")]
+    BadValue { field: &'static str, value: String },
+}
+
+impl NeighborTable {
+    /// Query the ARP cache using the `arp` command.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `arp` command fails to execute, or returns
+    /// unparseable output.
+    pub async fn load_from_arp() -> Result<Self, Error> {
+        let output = Command::new(ARP_PATH)
+            .arg("-an")
+            .stdin(std::process::Stdio::null())
+            .output()
+            .await
+            .map_err(Error::ArpExec)?;
+        if !output.status.success() {
+            return Err(Error::ArpFail(output.status));
+        }
+        Self::parse(&String::from_utf8(output.stdout).map_err(Error::ArpUtf8)?)
+    }
+
+    /// Parse the output of `arp -an`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an entry is unrecognized or contains unparseable
+    /// values
+    pub fn parse(output: &str) -> Result<Self, Error> {
+        let neighbors = output
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(parse_entry)
+            .collect::<Result<_, _>>()?;
+        Ok(NeighborTable { neighbors })
+    }
+
+    /// All entries, in listing order
+    pub fn iter(&self) -> impl Iterator<Item = &Neighbor> {
+        self.neighbors.iter()
+    }
+
+    /// Find the entry for `addr`, preferring one on `net_if` if given
+    #[must_use]
+    pub fn get(&self, addr: Ipv4Addr, net_if: Option<&str>) -> Option<&Neighbor> {
+        let mut matches = self.neighbors.iter().filter(|n| n.addr == addr);
+        match net_if {
+            Some(net_if) => matches
+                .clone()
+                .find(|n| n.net_if == net_if)
+                .or_else(|| matches.next()),
+            None => matches.next(),
+        }
+    }
+}
+
+impl RoutingTable {
+    /// Pair each route that goes through an IPv4 gateway with the gateway's
+    /// ARP cache entry.  Routes whose gateway isn't in the cache are skipped.
+    pub fn gateway_neighbors<'a>(
+        &'a self,
+        neighbors: &'a NeighborTable,
+    ) -> impl Iterator<Item = (&'a RouteEntry, &'a Neighbor)> {
+        self.routes().iter().filter_map(move |route| {
+            let Entity::Cidr(AnyIpCidr::V4(gateway)) = route.gateway.entity else {
+                return None;
+            };
+            if !gateway.is_host_address() {
+                return None;
+            }
+            neighbors
+                .get(gateway.first_address(), Some(&route.net_if))
+                .map(|neighbor| (route, neighbor))
+        })
+    }
+}
+
+/// Parse a single entry, e.g.,
+/// `? (192.168.64.1) at 16:9d:99:d7:7d:64 on en0 ifscope [ethernet]`
+fn parse_entry(line: &str) -> Result<Neighbor, Error> {
+    let bad_entry = || Error::BadEntry(line.into());
+    let (_, rest) = line.split_once('(').ok_or_else(bad_entry)?;
+    let (addr, rest) = rest.split_once(')').ok_or_else(bad_entry)?;
+    let addr = addr.parse().map_err(|_| Error::BadValue {
+        field: "address",
+        value: addr.into(),
+    })?;
+
+    let mut words = rest.split_ascii_whitespace();
+    let mut neighbor = Neighbor {
+        addr,
+        mac: None,
+        net_if: String::new(),
+        expires: None,
+        permanent: false,
+        ifscope: false,
+        link_type: None,
+    };
+    while let Some(word) = words.next() {
+        match word {
+            "at" => {
+                let mac = words.next().ok_or_else(bad_entry)?;
+                if mac != "(incomplete)" {
+                    neighbor.mac = Some(parse_mac(mac).map_err(|_| Error::BadValue {
+                        field: "MAC address",
+                        value: mac.into(),
+                    })?);
+                }
+            }
+            "on" => neighbor.net_if = words.next().ok_or_else(bad_entry)?.into(),
+            "ifscope" => neighbor.ifscope = true,
+            "permanent" => neighbor.permanent = true,
+            "expires" => {
+                // expires in N seconds
+                let secs = words.nth(1).ok_or_else(bad_entry)?;
+                neighbor.expires = Some(Duration::from_secs(secs.parse().map_err(|_| {
+                    Error::BadValue {
+                        field: "expiration",
+                        value: secs.into(),
+                    }
+                })?));
+            }
+            link_type if link_type.starts_with('[') => {
+                neighbor.link_type = Some(link_type.trim_matches(&['[', ']'][..]).into());
+            }
+            _ => (),
+        }
+    }
+    if neighbor.net_if.is_empty() {
+        return Err(bad_entry());
+    }
+    Ok(neighbor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, NeighborTable};
+    use crate::RoutingTable;
+    use std::time::Duration;
+
+    include!(concat!(env!("OUT_DIR"), "/sample_table.rs"));
+
+    #[test]
+    fn parse_arp() {
+        let neighbors = NeighborTable::parse(ARP_AN).expect("parse arp");
+        assert_eq!(neighbors.iter().count(), 7);
+
+        let router = neighbors
+            .get("192.168.64.1".parse().unwrap(), None)
+            .unwrap();
+        assert_eq!(router.mac.unwrap().to_string(), "16:9D:99:D7:7D:64");
+        assert_eq!(router.net_if, "en0");
+        assert!(router.ifscope);
+        assert_eq!(router.link_type.as_deref(), Some("ethernet"));
+
+        let expiring = neighbors
+            .get("192.168.64.7".parse().unwrap(), None)
+            .unwrap();
+        assert_eq!(expiring.expires, Some(Duration::from_secs(1181)));
+        let incomplete = neighbors
+            .get("192.168.64.9".parse().unwrap(), None)
+            .unwrap();
+        assert!(incomplete.mac.is_none());
+        let mdns = neighbors
+            .get("224.0.0.251".parse().unwrap(), Some("en0"))
+            .unwrap();
+        assert!(mdns.permanent);
+        let link_local = neighbors
+            .get("169.254.13.7".parse().unwrap(), None)
+            .unwrap();
+        assert!(!link_local.ifscope);
+    }
+
+    #[test]
+    fn gateway_neighbors() {
+        let neighbors = NeighborTable::parse(ARP_AN).unwrap();
+        let rt = RoutingTable::from_netstat_output(SAMPLE_TABLE).unwrap();
+        let pairs: Vec<_> = rt.gateway_neighbors(&neighbors).collect();
+        // The default route and the loopback routes through 127.0.0.1, which
+        // isn't in the ARP cache
+        assert_eq!(pairs.len(), 1);
+        let (route, neighbor) = pairs[0];
+        assert_eq!(route.dest.to_string(), "default");
+        assert_eq!(neighbor.addr.to_string(), "192.168.64.1");
+    }
+
+    #[test]
+    fn bad_arp() {
+        assert!(matches!(
+            NeighborTable::parse("? 192.168.64.1 at 0:0:0:0:0:1"),
+            Err(Error::BadEntry(_))
+        ));
+        assert!(matches!(
+            NeighborTable::parse("? (192.168.64.1) at zz:0:0:0:0:1 on en0"),
+            Err(Error::BadValue { .. })
+        ));
+    }
+}
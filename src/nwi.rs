@@ -0,0 +1,283 @@
+use crate::Protocol;
+use std::{net::IpAddr, process::ExitStatus, string::FromUtf8Error};
+use tokio::process::Command;
+
+const SCUTIL_PATH: &str = "/usr/sbin/scutil";
+
+/// The system's interface preferences, as listed by `scutil --nwi`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetworkInterfaceOrder {
+    /// IPv4 interfaces, most-preferred (primary) first
+    pub v4: Vec<NwiInterface>,
+
+    /// IPv6 interfaces, most-preferred (primary) first
+    pub v6: Vec<NwiInterface>,
+
+    /// Overall IPv4 reachability
+    pub v4_reach: Option<Reachability>,
+
+    /// Overall IPv6 reachability
+    pub v6_reach: Option<Reachability>,
+
+    /// All network interfaces, in service order
+    pub interfaces: Vec<String>,
+}
+
+/// A single interface's entry in the network information
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NwiInterface {
+    pub name: String,
+    pub flags: u32,
+    /// Names of the set flags, e.g., `IPv4` or `DNS`
+    pub flag_names: Vec<String>,
+    pub address: Option<IpAddr>,
+    pub vpn_server: Option<IpAddr>,
+    pub reach: Option<Reachability>,
+}
+
+/// Reachability flags, e.g., `0x00000002 (Reachable)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reachability {
+    pub flags: u32,
+    pub names: Vec<String>,
+}
+
+/// Various errors
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to execute {SCUTIL_PATH}: {0}")]
+    ScutilExec(std::io::Error),
+    #[error("failed to get network information: {0}")]
+    ScutilFail(ExitStatus),
+    #[error("scutil output not UTF-8")]
+    ScutilUtf8(FromUtf8Error),
+    #[error("interface details found outside an interface: {0:?}")]
+    DetailBeforeInterface(String),
+    #[error("invalid {field} {value:?} in network information")]
+    BadValue { field: &'static str, value: String },
+}
+
+impl NetworkInterfaceOrder {
+    /// Query the network information using `scutil --nwi`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `scutil` command fails to execute, or returns
+    /// unparseable output.
+    pub async fn load_from_scutil() -> Result<Self, Error> {
+        let output = Command::new(SCUTIL_PATH)
+            .arg("--nwi")
+            .stdin(std::process::Stdio::null())
+            .output()
+            .await
+            .map_err(Error::ScutilExec)?;
+        if !output.status.success() {
+            return Err(Error::ScutilFail(output.status));
+        }
+        Self::parse(&String::from_utf8(output.stdout).map_err(Error::ScutilUtf8)?)
+    }
+
+    /// Parse the output of `scutil --nwi`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the output contains unparseable values
+    pub fn parse(output: &str) -> Result<Self, Error> {
+        let mut order = NetworkInterfaceOrder::default();
+        let mut proto = None;
+        for line in output.lines() {
+            let trimmed = line.trim();
+            if trimmed.starts_with("IPv4 network interface") {
+                proto = Some(Protocol::V4);
+                continue;
+            }
+            if trimmed.starts_with("IPv6 network interface") {
+                proto = Some(Protocol::V6);
+                continue;
+            }
+            if let Some(interfaces) = trimmed.strip_prefix("Network interfaces:") {
+                order.interfaces = interfaces
+                    .split_ascii_whitespace()
+                    .map(ToOwned::to_owned)
+                    .collect();
+                proto = None;
+                continue;
+            }
+            let Some(proto) = proto else {
+                continue;
+            };
+            let (list, reach) = match proto {
+                Protocol::V4 => (&mut order.v4, &mut order.v4_reach),
+                Protocol::V6 => (&mut order.v6, &mut order.v6_reach),
+            };
+
+            if let Some(flags) = trimmed.strip_prefix("REACH : flags") {
+                *reach = Some(parse_reachability(flags)?);
+                continue;
+            }
+            let Some((name, rest)) = trimmed.split_once(':') else {
+                // e.g., "No IPv6 states found"
+                continue;
+            };
+            let (name, rest) = (name.trim(), rest.trim());
+            // An interface's first line is `en0 : flags : 0x5 (IPv4,DNS)`
+            if let Some(flags) = rest.strip_prefix("flags") {
+                let (flags, flag_names) = parse_flags(flags.trim_start_matches([' ', ':']))?;
+                list.push(NwiInterface {
+                    name: name.into(),
+                    flags,
+                    flag_names,
+                    address: None,
+                    vpn_server: None,
+                    reach: None,
+                });
+                continue;
+            }
+            let interface = list
+                .last_mut()
+                .ok_or_else(|| Error::DetailBeforeInterface(line.into()))?;
+            match name {
+                "address" => interface.address = Some(parse_addr("address", rest)?),
+                "VPN server" => interface.vpn_server = Some(parse_addr("VPN server", rest)?),
+                "reach" => interface.reach = Some(parse_reachability(rest)?),
+                _ => (),
+            }
+        }
+        Ok(order)
+    }
+
+    /// The interfaces for `proto`, most-preferred first
+    #[must_use]
+    pub fn interfaces_for(&self, proto: Protocol) -> &[NwiInterface] {
+        match proto {
+            Protocol::V4 => &self.v4,
+            Protocol::V6 => &self.v6,
+        }
+    }
+
+    /// The primary interface for `proto`, if any
+    #[must_use]
+    pub fn primary(&self, proto: Protocol) -> Option<&NwiInterface> {
+        self.interfaces_for(proto).first()
+    }
+
+    /// Position of `net_if` in the preference order for `proto`, with 0 being
+    /// the primary interface.  `None` if it isn't listed.
+    #[must_use]
+    pub fn rank(&self, proto: Protocol, net_if: &str) -> Option<usize> {
+        self.interfaces_for(proto)
+            .iter()
+            .position(|interface| interface.name == net_if)
+    }
+}
+
+/// Parse `0x5 (IPv4,DNS)` into the numeric flags and their names
+fn parse_flags(s: &str) -> Result<(u32, Vec<String>), Error> {
+    let s = s.trim();
+    let (value, names) = s.split_once(' ').unwrap_or((s, ""));
+    let flags =
+        u32::from_str_radix(value.trim_start_matches("0x"), 16).map_err(|_| Error::BadValue {
+            field: "flags",
+            value: value.into(),
+        })?;
+    let names = names
+        .trim()
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .split(',')
+        .filter(|name| !name.is_empty())
+        .map(ToOwned::to_owned)
+        .collect();
+    Ok((flags, names))
+}
+
+pub(crate) fn parse_reachability(s: &str) -> Result<Reachability, Error> {
+    let (flags, names) = parse_flags(s)?;
+    Ok(Reachability { flags, names })
+}
+
+fn parse_addr(field: &'static str, value: &str) -> Result<IpAddr, Error> {
+    value.parse().map_err(|_| Error::BadValue {
+        field,
+        value: value.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, NetworkInterfaceOrder};
+    use crate::{LookupOptions, Protocol, RoutingTable};
+
+    include!(concat!(env!("OUT_DIR"), "/sample_table.rs"));
+
+    #[test]
+    fn parse_nwi() {
+        let order = NetworkInterfaceOrder::parse(SCUTIL_NWI).expect("parse scutil --nwi");
+        assert_eq!(order.primary(Protocol::V4).unwrap().name, "en0");
+        assert_eq!(order.rank(Protocol::V6, "utun2"), Some(0));
+        assert_eq!(order.rank(Protocol::V6, "utun0"), Some(1));
+        assert_eq!(order.rank(Protocol::V6, "utun1"), None);
+
+        let utun2 = order.primary(Protocol::V6).unwrap();
+        assert_eq!(utun2.flags, 6);
+        assert_eq!(utun2.flag_names, ["IPv6", "DNS"]);
+        assert_eq!(utun2.vpn_server, Some("203.0.113.10".parse().unwrap()));
+        assert_eq!(utun2.reach.as_ref().unwrap().flags, 3);
+        assert_eq!(order.v4_reach.unwrap().names, ["Reachable"]);
+        assert_eq!(order.interfaces, ["en0", "utun0", "utun1", "utun2"]);
+    }
+
+    #[test]
+    fn no_ipv6_states() {
+        let input = SCUTIL_NWI
+            .split("IPv6 network interface information")
+            .next()
+            .unwrap()
+            .to_owned()
+            + "IPv6 network interface information\n   No IPv6 states found\n\n   REACH : flags 0x00000000 (Not Reachable)\n";
+        let order = NetworkInterfaceOrder::parse(&input).unwrap();
+        assert!(order.v6.is_empty());
+        assert_eq!(order.v6_reach.unwrap().flags, 0);
+    }
+
+    #[test]
+    fn tie_break() {
+        let order = NetworkInterfaceOrder::parse(SCUTIL_NWI).unwrap();
+        let rt = RoutingTable::from_netstat_output(SAMPLE_TABLE).unwrap();
+        let addr = "2606:4700:4700::1111".parse().unwrap();
+
+        // Table order picks the first of the three default routes
+        assert_eq!(rt.find_route_entry(addr).unwrap().net_if, "utun0");
+        let options = LookupOptions {
+            interface_order: Some(&order),
+            ..LookupOptions::default()
+        };
+        assert_eq!(
+            rt.find_route_entry_with(addr, &options).unwrap().net_if,
+            "utun2"
+        );
+
+        // Routes that aren't tied are unaffected
+        let addr = "fe80::1".parse().unwrap();
+        assert_eq!(
+            rt.find_route_entry_with(addr, &options).unwrap().dest,
+            rt.find_route_entry(addr).unwrap().dest
+        );
+    }
+
+    #[test]
+    fn bad_nwi() {
+        assert!(matches!(
+            NetworkInterfaceOrder::parse(
+                "IPv4 network interface information\n  address : 1.2.3.4\n"
+            ),
+            Err(Error::DetailBeforeInterface(_))
+        ));
+        assert!(matches!(
+            NetworkInterfaceOrder::parse(
+                "IPv4 network interface information\n  en0 : flags : 0xZ (IPv4)\n"
+            ),
+            Err(Error::BadValue { field: "flags", .. })
+        ));
+    }
+}
@@ -0,0 +1,380 @@
+use crate::{
+    iproute2::DISCARD_INTERFACE, route_entry::parse_netmask, Destination, Entity, LinkRef,
+    Protocol, RouteEntry, RoutingFlag, RoutingTable,
+};
+use cidr::AnyIpCidr;
+use std::{
+    collections::HashSet,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::SplitAsciiWhitespace,
+};
+
+const PROC_NET_ROUTE: &str = "/proc/net/route";
+const PROC_NET_IPV6_ROUTE: &str = "/proc/net/ipv6_route";
+
+/// Route flag bits (see `<linux/route.h>`) and their equivalents
+const RTF_FLAGS: &[(u32, RoutingFlag)] = &[
+    (0x0001, RoutingFlag::Up),
+    (0x0002, RoutingFlag::Gateway),
+    (0x0004, RoutingFlag::Host),
+    (0x0010, RoutingFlag::Dynamic),
+    (0x0020, RoutingFlag::Modified),
+    (0x0200, RoutingFlag::Reject),
+];
+
+/// Flag bit of IPv6 routes to the host's own addresses
+const RTF_LOCAL: u32 = 0x8000_0000;
+
+/// Various errors
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("reading {path}: {err}")]
+    Read {
+        path: &'static str,
+        err: std::io::Error,
+    },
+    #[error("{file} line {line}: missing {field}")]
+    MissingField {
+        file: &'static str,
+        line: usize,
+        field: &'static str,
+    },
+    #[error("{file} line {line}: invalid {field} {value:?}")]
+    BadField {
+        file: &'static str,
+        line: usize,
+        field: &'static str,
+        value: String,
+    },
+}
+
+impl RoutingTable {
+    /// Read the routing table from Linux's `/proc/net/route` and
+    /// `/proc/net/ipv6_route`, for systems without `ip`.  A missing
+    /// `/proc/net/ipv6_route` (i.e., IPv6 is disabled) is treated as empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `/proc/net/route` can't be read, or either file is
+    /// unparseable
+    pub async fn load_from_procfs() -> Result<Self, Error> {
+        let route = tokio::fs::read_to_string(PROC_NET_ROUTE)
+            .await
+            .map_err(|err| Error::Read {
+                path: PROC_NET_ROUTE,
+                err,
+            })?;
+        let ipv6_route = match tokio::fs::read_to_string(PROC_NET_IPV6_ROUTE).await {
+            Ok(ipv6_route) => ipv6_route,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => {
+                return Err(Error::Read {
+                    path: PROC_NET_IPV6_ROUTE,
+                    err,
+                })
+            }
+        };
+        Self::from_procfs(&route, &ipv6_route)
+    }
+
+    /// Generate a `RoutingTable` from the contents of `/proc/net/route`
+    /// (the main IPv4 table) and `/proc/net/ipv6_route` (every IPv6 table).
+    /// Either may be empty.  Metrics become priorities, and routes without an
+    /// interface are put on `lo`, as iproute2 shows them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a route is unparseable
+    pub fn from_procfs(route: &str, ipv6_route: &str) -> Result<RoutingTable, Error> {
+        let mut routes = vec![];
+        for (i, line) in route.lines().enumerate() {
+            if i == 0 && line.starts_with("Iface") || line.trim().is_empty() {
+                continue;
+            }
+            routes.push(parse_route(&mut Row::new(PROC_NET_ROUTE, i, line))?);
+        }
+        for (i, line) in ipv6_route.lines().enumerate() {
+            if !line.trim().is_empty() {
+                routes.push(parse_ipv6_route(&mut Row::new(
+                    PROC_NET_IPV6_ROUTE,
+                    i,
+                    line,
+                ))?);
+            }
+        }
+        Ok(RoutingTable::from_routes(routes))
+    }
+}
+
+/// The whitespace-separated fields of a line, with its location for errors
+struct Row<'a> {
+    file: &'static str,
+    line: usize,
+    fields: SplitAsciiWhitespace<'a>,
+}
+
+impl<'a> Row<'a> {
+    fn new(file: &'static str, index: usize, line: &'a str) -> Self {
+        Row {
+            file,
+            line: index + 1,
+            fields: line.split_ascii_whitespace(),
+        }
+    }
+
+    fn field(&mut self, field: &'static str) -> Result<&'a str, Error> {
+        self.fields.next().ok_or(Error::MissingField {
+            file: self.file,
+            line: self.line,
+            field,
+        })
+    }
+
+    fn parse_with<T>(
+        &mut self,
+        field: &'static str,
+        parse: impl FnOnce(&str) -> Option<T>,
+    ) -> Result<T, Error> {
+        let value = self.field(field)?;
+        parse(value).ok_or_else(|| self.bad(field, value))
+    }
+
+    fn bad(&self, field: &'static str, value: &str) -> Error {
+        Error::BadField {
+            file: self.file,
+            line: self.line,
+            field,
+            value: value.into(),
+        }
+    }
+}
+
+fn hex_u32(s: &str) -> Option<u32> {
+    u32::from_str_radix(s, 16).ok()
+}
+
+/// An IPv4 address, printed as a little-endian hex word
+fn hex_ipv4(s: &str) -> Option<Ipv4Addr> {
+    hex_u32(s).map(|addr| Ipv4Addr::from(addr.to_le_bytes()))
+}
+
+fn hex_ipv6(s: &str) -> Option<Ipv6Addr> {
+    u128::from_str_radix(s, 16).ok().map(Ipv6Addr::from)
+}
+
+/// Parse a line of `/proc/net/route`
+fn parse_route(row: &mut Row) -> Result<RouteEntry, Error> {
+    let net_if = interface(row.field("Iface")?);
+    let dest = row.parse_with("Destination", hex_ipv4)?;
+    let gateway = row.parse_with("Gateway", hex_ipv4)?;
+    let bits = row.parse_with("Flags", hex_u32)?;
+    let refs = row.parse_with("RefCnt", |s| s.parse().ok())?;
+    let use_count = row.parse_with("Use", |s| s.parse().ok())?;
+    let metric = row.parse_with("Metric", |s| s.parse().ok())?;
+    let mask = row.field("Mask")?;
+    let len = hex_ipv4(mask)
+        .and_then(|mask| parse_netmask(&mask.to_string()).ok())
+        .ok_or_else(|| row.bad("Mask", mask))?;
+    let mtu = row.parse_with("MTU", |s| s.parse().ok())?;
+
+    Ok(RouteEntry {
+        proto: Protocol::V4,
+        dest: destination(row, IpAddr::V4(dest), len)?,
+        gateway: gateway_destination(IpAddr::V4(gateway), &net_if),
+        flags: flags(bits),
+        net_if,
+        expires: None,
+        refs: Some(refs),
+        use_count: Some(use_count),
+        // Zero means the interface's MTU
+        mtu: Some(mtu).filter(|&mtu| mtu != 0),
+        priority: Some(metric),
+        label: None,
+        origin: None,
+        scope: None,
+        route_type: None,
+    })
+}
+
+/// Parse a line of `/proc/net/ipv6_route`
+fn parse_ipv6_route(row: &mut Row) -> Result<RouteEntry, Error> {
+    let dest = row.parse_with("destination", hex_ipv6)?;
+    let len = row.parse_with("destination length", |s| u8::from_str_radix(s, 16).ok())?;
+    // Source routing isn't supported
+    row.field("source")?;
+    row.field("source length")?;
+    let gateway = row.parse_with("next hop", hex_ipv6)?;
+    let metric = row.parse_with("metric", hex_u32)?;
+    let refs = row.parse_with("reference count", hex_u32)?;
+    let use_count = row.parse_with("use count", hex_u32)?;
+    let bits = row.parse_with("flags", hex_u32)?;
+    let net_if = interface(row.field("device")?);
+
+    let mut flags = flags(bits);
+    if bits & RTF_LOCAL != 0 {
+        flags.insert(RoutingFlag::Local);
+    }
+    Ok(RouteEntry {
+        proto: Protocol::V6,
+        dest: destination(row, IpAddr::V6(dest), len)?,
+        gateway: gateway_destination(IpAddr::V6(gateway), &net_if),
+        flags,
+        net_if,
+        expires: None,
+        refs: Some(refs.into()),
+        use_count: Some(use_count.into()),
+        mtu: None,
+        priority: Some(metric),
+        label: None,
+        origin: None,
+        scope: None,
+        route_type: None,
+    })
+}
+
+/// The kernel prints `*` for routes without an interface
+fn interface(name: &str) -> String {
+    match name {
+        "*" => DISCARD_INTERFACE.into(),
+        name => name.into(),
+    }
+}
+
+fn flags(bits: u32) -> HashSet<RoutingFlag> {
+    RTF_FLAGS
+        .iter()
+        .filter(|(bit, _)| bits & bit != 0)
+        .map(|(_, flag)| flag.clone())
+        .collect()
+}
+
+/// The destination network, or the default route for `0.0.0.0/0` and `::/0`
+fn destination(row: &Row, addr: IpAddr, len: u8) -> Result<Destination, Error> {
+    let entity = if len == 0 && addr.is_unspecified() {
+        Entity::Default
+    } else {
+        Entity::Cidr(
+            AnyIpCidr::new(addr, len)
+                .map_err(|_| row.bad("destination", &format!("{addr}/{len}")))?,
+        )
+    };
+    Ok(Destination { entity, zone: None })
+}
+
+/// A gateway address, where the unspecified address means the route is
+/// directly on its interface
+fn gateway_destination(gateway: IpAddr, net_if: &str) -> Destination {
+    let entity = if gateway.is_unspecified() {
+        Entity::Link(LinkRef::Name(net_if.into()))
+    } else {
+        Entity::Cidr(AnyIpCidr::new_host(gateway))
+    };
+    Destination { entity, zone: None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Error;
+    use crate::{Entity, LinkRef, Protocol, RoutingFlag, RoutingTable, RoutingTableSet};
+
+    include!(concat!(env!("OUT_DIR"), "/sample_table.rs"));
+
+    #[test]
+    fn ipv4() {
+        let rt = RoutingTable::from_procfs(PROC_NET_ROUTE, "").unwrap();
+        assert_eq!(rt.routes().len(), 7);
+
+        // The lower metric wins
+        let entry = rt.find_route_entry("1.1.1.1".parse().unwrap()).unwrap();
+        assert_eq!(entry.dest.entity, Entity::Default);
+        assert_eq!(entry.gateway.entity.to_string(), "192.168.1.1");
+        assert_eq!(entry.net_if, "eth0");
+        assert_eq!(entry.priority, Some(100));
+        assert!(
+            entry.flags.contains(&RoutingFlag::Up) && entry.flags.contains(&RoutingFlag::Gateway)
+        );
+
+        let entry = rt.find_route_entry("10.8.0.9".parse().unwrap()).unwrap();
+        assert_eq!(entry.dest.entity.to_string(), "10.8.0.0/24");
+        assert_eq!(
+            entry.gateway.entity,
+            Entity::Link(LinkRef::Name("wlan0".into()))
+        );
+
+        let entry = rt.find_route_entry("10.66.0.5".parse().unwrap()).unwrap();
+        assert!(entry.flags.contains(&RoutingFlag::Host));
+        assert_eq!(
+            (entry.refs, entry.use_count, entry.mtu),
+            (Some(1), Some(42), Some(1380))
+        );
+
+        let entry = rt.find_route_entry("10.99.1.1".parse().unwrap()).unwrap();
+        assert!(entry.flags.contains(&RoutingFlag::Reject));
+        assert_eq!(entry.net_if, "lo");
+
+        // The same lookups as from iproute2's view of the main table
+        let set = RoutingTableSet::from_iproute2_json(IP_ROUTE_SHOW_TABLE_ALL).unwrap();
+        let main = set.get(254).unwrap();
+        for addr in ["1.1.1.1", "10.8.0.9", "192.168.1.7", "172.17.0.2"] {
+            let addr = addr.parse().unwrap();
+            let (procfs, iproute2) = (
+                rt.find_route_entry(addr).unwrap(),
+                main.find_route_entry(addr).unwrap(),
+            );
+            assert_eq!(procfs.to_string(), iproute2.to_string());
+            assert_eq!(procfs.priority, iproute2.priority);
+        }
+    }
+
+    #[test]
+    fn ipv6() {
+        let rt = RoutingTable::from_procfs("", PROC_NET_IPV6_ROUTE).unwrap();
+        assert!(rt.routes().iter().all(|route| route.proto == Protocol::V6));
+
+        // The real default route wins over the catch-all unreachable one
+        let entry = rt
+            .find_route_entry("2606:4700::1111".parse().unwrap())
+            .unwrap();
+        assert_eq!(entry.gateway.entity.to_string(), "fe80::1");
+        assert_eq!(entry.net_if, "eth0");
+        assert_eq!(entry.priority, Some(100));
+
+        let entry = rt
+            .find_route_entry("2001:db8:dead::1".parse().unwrap())
+            .unwrap();
+        assert!(entry.flags.contains(&RoutingFlag::Reject));
+        let entry = rt
+            .find_route_entry("2001:db8:1::50".parse().unwrap())
+            .unwrap();
+        assert!(entry.flags.contains(&RoutingFlag::Local));
+        let entry = rt.find_route_entry("fe80::1234".parse().unwrap()).unwrap();
+        assert_eq!(entry.net_if, "eth0");
+        assert_eq!(entry.dest.entity.to_string(), "fe80::/64");
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(
+            RoutingTable::from_procfs("Iface\tDestination\neth0\t0000000G\n", ""),
+            Err(Error::BadField {
+                line: 2,
+                field: "Destination",
+                ..
+            })
+        ));
+        assert!(matches!(
+            RoutingTable::from_procfs(
+                "eth0\t00000000\t00000000\t0001\t0\t0\t0\t00FF00FF\t0\t0\t0\n",
+                ""
+            ),
+            Err(Error::BadField { field: "Mask", .. })
+        ));
+        assert!(matches!(
+            RoutingTable::from_procfs("", "00000000000000000000000000000000 00\n"),
+            Err(Error::MissingField {
+                field: "source",
+                ..
+            })
+        ));
+    }
+}
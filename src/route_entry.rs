@@ -1,8 +1,11 @@
-use crate::{Destination, Entity, Protocol, RoutingFlag};
-use cidr::AnyIpCidr;
+use crate::{
+    columns::Columns, Destination, Entity, LinkRef, NetstatPlatform, Protocol, RoutingFlag,
+};
+use cidr::{AnyIpCidr, Ipv6Cidr};
 use mac_address::MacAddress;
 use std::{
     collections::HashSet,
+    convert::TryFrom,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
     time::Duration,
 };
@@ -25,8 +28,35 @@ pub struct RouteEntry {
     /// Network interface that holds this route
     pub net_if: String,
 
-    /// RouteEntry expiration.  This is primarily seen for ARP-derived entries
+    /// `RouteEntry` expiration.  This is primarily seen for ARP-derived entries
     pub expires: Option<Duration>,
+
+    /// Number of references held on the route (`netstat -rnl` only)
+    pub refs: Option<u64>,
+
+    /// Number of packets sent using the route (`netstat -rnl` only)
+    pub use_count: Option<u64>,
+
+    /// MTU pinned on the route (`netstat -rnl` only)
+    pub mtu: Option<u32>,
+
+    /// Route priority (OpenBSD `Prio`, or Linux metric).  Among routes to the
+    /// same destination, the one with the lowest priority is used.
+    pub priority: Option<u32>,
+
+    /// Route label (OpenBSD only)
+    pub label: Option<String>,
+
+    /// What installed the route (Linux only, e.g., `kernel`, `dhcp` or `ra`)
+    pub origin: Option<String>,
+
+    /// Scope of the destination (Linux only, e.g., `link` or `host`)
+    pub scope: Option<String>,
+
+    /// Route type, when not an ordinary unicast route (Linux only, e.g.,
+    /// `local` or `throw`).  Types with a `RoutingFlag` equivalent also set
+    /// the flag.
+    pub route_type: Option<String>,
 }
 
 impl std::fmt::Display for RouteEntry {
@@ -39,6 +69,14 @@ impl std::fmt::Display for RouteEntry {
             flags,
             net_if,
             expires,
+            refs,
+            use_count,
+            mtu,
+            priority,
+            label,
+            origin,
+            scope,
+            route_type,
         } = self;
         write!(f, "{proto:?}({dest} -> {gateway} if={net_if}")
     }
@@ -67,12 +105,25 @@ pub enum Error {
     #[error("invalid number of IPv4 address components ({n_comps}) in {addr:?}")]
     ParseIPv4AddrNComps { n_comps: usize, addr: String },
 
+    #[error("invalid netmask {mask:?}")]
+    ParseNetmask { mask: String },
+
+    #[error("non-contiguous netmask {mask:?}")]
+    NonContiguousNetmask { mask: String },
+
     #[error("invalid expiration {expiration:?}: {err}")]
     ParseExpiration {
         expiration: String,
         err: std::num::ParseIntError,
     },
 
+    #[error("invalid {column} value {value:?}: {err}")]
+    ParseCounter {
+        column: &'static str,
+        value: String,
+        err: std::num::ParseIntError,
+    },
+
     #[error("missing destination")]
     MissingDestination,
 
@@ -85,40 +136,85 @@ pub enum Error {
 
 impl RouteEntry {
     /// Parse a textual route entry from the netstat output, specifying the
-    /// current protocols and active column headers.
-    pub(crate) fn parse(proto: Protocol, line: &str, headers: &[&str]) -> Result<Self, Error> {
-        let fields: Vec<String> = line.split_ascii_whitespace().map(str::to_string).collect();
+    /// current protocols, the active columns and the platform whose flag
+    /// letters to use.  A blank `Gateway` cell marks a route directly on its
+    /// interface, which gets the interface as its link gateway.
+    pub(crate) fn parse(
+        proto: Protocol,
+        line: &str,
+        columns: &Columns,
+        platform: NetstatPlatform,
+    ) -> Result<Self, Error> {
         let mut flags = HashSet::new();
         let mut dest = None;
         let mut gateway = None;
         let mut net_if: Option<String> = None;
         let mut expires = None;
+        let mut refs = None;
+        let mut use_count = None;
+        let mut mtu = None;
+        let mut priority = None;
+        let mut label = None;
+        let mut netmask = None;
 
-        // Scan through the fields, matching them up with the headers.
-        for (header, field) in headers.iter().zip(fields) {
-            match *header {
-                "Destination" => dest = Some(parse_destination(&field)?),
-                "Gateway" => gateway = Some(parse_destination(&field)?),
-                "Flags" => flags = parse_flags(&field),
-                "Netif" => net_if = Some(field),
-                "Expire" => expires = parse_expire(&field)?,
+        // Scan through the cells, matching them up with their columns.
+        for (header, field) in columns.split(line) {
+            match header {
+                "Destination" => dest = Some(parse_destination(field)?),
+                "Gateway" => gateway = Some(field),
+                "Netmask" => netmask = Some(field),
+                "Flags" => flags = parse_flags(field, platform),
+                // OpenBSD calls the interface column `Iface`, and NetBSD
+                // `Interface`
+                "Netif" | "Iface" | "Interface" => net_if = Some(field.to_owned()),
+                "Expire" => expires = parse_expire(field)?,
+                "Refs" => refs = parse_counter("Refs", field)?,
+                "Use" => use_count = parse_counter("Use", field)?,
+                "Mtu" => mtu = parse_counter("Mtu", field)?,
+                "Prio" => priority = parse_counter("Prio", field)?,
+                "Label" if !field.is_empty() => label = Some(field.to_owned()),
                 _ => (),
             }
         }
 
+        let dest = with_netmask(dest.ok_or(Error::MissingDestination)?, netmask)?;
+        let gateway = match (gateway, &net_if) {
+            // Some releases print the interface as the gateway of routes
+            // directly on it
+            (Some(gateway), Some(net_if)) if gateway == net_if => Destination {
+                entity: Entity::Link(LinkRef::Name(net_if.clone())),
+                zone: None,
+            },
+            (Some(gateway), _) => parse_destination(gateway)?,
+            (None, Some(net_if)) if columns.names().any(|name| name == "Gateway") => Destination {
+                entity: Entity::Link(LinkRef::Name(net_if.clone())),
+                zone: None,
+            },
+            (None, _) => return Err(Error::MissingGateway),
+        };
+
         let route = RouteEntry {
             proto,
-            dest: dest.ok_or(Error::MissingDestination)?,
-            gateway: gateway.ok_or(Error::MissingGateway)?,
+            dest,
+            gateway,
             flags,
             net_if: net_if.ok_or(Error::MissingInterface)?,
             expires,
+            refs,
+            use_count,
+            mtu,
+            priority,
+            label,
+            origin: None,
+            scope: None,
+            route_type: None,
         };
         Ok(route)
     }
 
     /// Return whether the specified route's destination is appropriate for the given address
-    pub(crate) fn contains(&self, addr: IpAddr) -> bool {
+    #[must_use]
+    pub fn contains(&self, addr: IpAddr) -> bool {
         match self.dest.entity {
             Entity::Cidr(cidr) => cidr.contains(&addr),
             Entity::Default => match self.gateway.entity {
@@ -137,7 +233,8 @@ impl RouteEntry {
     /// Compare two routes, returning the one that is more-precise based on whether
     /// it resolves to an identified device or interface, or has a larger network
     /// length
-    pub(crate) fn most_precise<'a>(&'a self, other: &'a Self) -> &'a Self {
+    #[must_use]
+    pub fn most_precise<'a>(&'a self, other: &'a Self) -> &'a Self {
         match self.dest.entity {
             // If this is a hardware address, we already know it's on the same
             // local network, and it's in the ARP table
@@ -179,14 +276,18 @@ impl RouteEntry {
     }
 }
 
-fn parse_destination(dest: &str) -> Result<Destination, Error> {
+pub(crate) fn parse_destination(dest: &str) -> Result<Destination, Error> {
     if dest.starts_with("link") {
+        let link = dest
+            .strip_prefix("link#")
+            .and_then(|index| index.parse().ok())
+            .map_or_else(|| LinkRef::Name(dest.to_owned()), LinkRef::Index);
         return Ok(Destination {
-            entity: Entity::Link(dest.to_owned()),
+            entity: Entity::Link(link),
             zone: None,
         });
     }
-    Ok(if let Some((addr, zone_etc)) = dest.split_once('%') {
+    let mut dest = if let Some((addr, zone_etc)) = dest.split_once('%') {
         // This route contains a zone ID
         // See: https://superuser.com/questions/99746/why-is-there-a-percent-sign-in-the-ipv6-address
         let addr: AnyIpCidr = addr.parse().map_err(|err| Error::ParseDestination {
@@ -198,7 +299,7 @@ fn parse_destination(dest: &str) -> Result<Destination, Error> {
 
         if let Some(bits) = zone_etc.next() {
             // Just reassemble it without the %zone and run it through the regular parser
-            let s = format!("{addr}{bits}");
+            let s = format!("{addr}/{bits}");
             Destination {
                 entity: parse_simple_destination(&s)?,
                 zone,
@@ -214,33 +315,47 @@ fn parse_destination(dest: &str) -> Result<Destination, Error> {
             entity: parse_simple_destination(dest)?,
             zone: None,
         }
-    })
+    };
+    decode_embedded_scope(&mut dest);
+    Ok(dest)
+}
+
+/// KAME-derived kernels embed the scope ID of link-local and
+/// interface/link-local multicast addresses in their second 16-bit word, and
+/// it sometimes leaks out (e.g., `fe80:4::1` for `fe80::1%4`).  Move it into
+/// the zone, keeping any zone given explicitly, and clear it from the address.
+fn decode_embedded_scope(dest: &mut Destination) {
+    let Entity::Cidr(AnyIpCidr::V6(cidr)) = dest.entity else {
+        return;
+    };
+    let mut segments = cidr.first_address().segments();
+    let scoped = segments[0] & 0xffc0 == 0xfe80 || matches!(segments[0] & 0xff0f, 0xff01 | 0xff02);
+    if !scoped || segments[1] == 0 {
+        return;
+    }
+    let scope = std::mem::take(&mut segments[1]);
+    if let Ok(cidr) = Ipv6Cidr::new(segments.into(), cidr.network_length()) {
+        dest.entity = Entity::Cidr(AnyIpCidr::V6(cidr));
+        dest.zone.get_or_insert_with(|| scope.to_string());
+    }
 }
 
 fn parse_simple_destination(dest: &str) -> Result<Entity, Error> {
     Ok(match dest {
         "default" => Entity::Default,
-
-        cidr if cidr.contains('/') => {
-            Entity::Cidr(cidr.parse().map_err(|err| Error::ParseDestination {
-                value: cidr.into(),
-                err,
-            })?)
-        }
+        cidr if cidr.contains('/') => Entity::Cidr(parse_cidr(cidr)?),
         // IPv4 host
         addr if addr.contains('.') => {
-            if let Ok(ipv4addr) = parse_ipv4dest(addr) {
-                Entity::Cidr(AnyIpCidr::new_host(IpAddr::V4(ipv4addr)))
+            if let Ok(cidr) = parse_ipv4dest(addr) {
+                Entity::Cidr(cidr)
             } else {
                 // Bridge broadcast addresses sometimes contain a dot-delimited MAC address
-                Entity::Mac(
-                    addr.replace('.', ":")
-                        .parse::<MacAddress>()
-                        .map_err(|err| Error::ParseMacAddr {
-                            dest: addr.into(),
-                            err,
-                        })?,
-                )
+                Entity::Mac(parse_mac(&addr.replace('.', ":")).map_err(|err| {
+                    Error::ParseMacAddr {
+                        dest: addr.into(),
+                        err,
+                    }
+                })?)
             }
         }
         // IPv6 host
@@ -249,22 +364,108 @@ fn parse_simple_destination(dest: &str) -> Result<Entity, Error> {
                 Entity::Cidr(AnyIpCidr::new_host(IpAddr::V6(v6addr)))
             } else {
                 // Try as a MAC address
-                Entity::Mac(
-                    addr.parse::<MacAddress>()
-                        .map_err(|err| Error::ParseMacAddr {
-                            dest: addr.into(),
-                            err,
-                        })?,
-                )
+                Entity::Mac(parse_mac(addr).map_err(|err| Error::ParseMacAddr {
+                    dest: addr.into(),
+                    err,
+                })?)
             }
         }
         // Match bare numbers
-        num => Entity::Cidr(AnyIpCidr::new_host(IpAddr::V4(parse_ipv4dest(num)?))),
+        num => Entity::Cidr(parse_ipv4dest(num)?),
     })
 }
 
-fn parse_flags(flags_s: &str) -> HashSet<RoutingFlag> {
-    flags_s.chars().map(RoutingFlag::from).collect()
+/// Whether `name` looks like a BSD interface name: a driver name followed by a
+/// unit number, e.g., `en0` or `bridge100`
+pub(crate) fn is_interface_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.ends_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Parse a colon-delimited MAC address.  BSD tools omit the leading zero of
+/// each octet (e.g., `1:0:5e:0:0:fb`), so pad them before parsing.
+pub(crate) fn parse_mac(addr: &str) -> Result<MacAddress, mac_address::MacParseError> {
+    addr.split(':')
+        .map(|octet| format!("{octet:0>2}"))
+        .collect::<Vec<_>>()
+        .join(":")
+        .parse()
+}
+
+/// Parse a CIDR, where the network length may also be written as a netmask in
+/// dotted-quad or hex notation (e.g., `10.0.0.0/255.0.0.0` or
+/// `10.0.0.0/0xff000000`)
+fn parse_cidr(cidr: &str) -> Result<AnyIpCidr, Error> {
+    let parse_error = |err| Error::ParseDestination {
+        value: cidr.into(),
+        err,
+    };
+    if let Some((addr, mask)) = cidr
+        .split_once('/')
+        .filter(|(_, mask)| !mask.bytes().all(|b| b.is_ascii_digit()))
+    {
+        if let Entity::Cidr(addr) = parse_simple_destination(addr)? {
+            if let Some(addr) = addr.first_address() {
+                return AnyIpCidr::new(addr, parse_netmask(mask)?).map_err(parse_error);
+            }
+        }
+    }
+    cidr.parse().map_err(parse_error)
+}
+
+/// Convert a netmask in dotted-quad, hex (`0xffffff00`) or IPv6 notation to a
+/// network length.  `route` prints an all-zeros mask as `default`.
+pub(crate) fn parse_netmask(mask: &str) -> Result<u8, Error> {
+    let invalid = || Error::ParseNetmask { mask: mask.into() };
+    let (bits, width) = if mask == "default" {
+        return Ok(0);
+    } else if let Some(hex) = mask.strip_prefix("0x") {
+        (
+            u128::from(u32::from_str_radix(hex, 16).map_err(|_| invalid())?),
+            32,
+        )
+    } else if let Ok(v4) = mask.parse::<Ipv4Addr>() {
+        (u128::from(u32::from(v4)), 32)
+    } else {
+        (
+            u128::from(mask.parse::<Ipv6Addr>().map_err(|_| invalid())?),
+            128,
+        )
+    };
+    // Left-align the mask, then make sure it's all ones followed by all zeros
+    let bits = bits << (128 - width);
+    let len = bits.leading_ones();
+    if len + bits.trailing_zeros() == 128 {
+        u8::try_from(len).map_err(|_| invalid())
+    } else {
+        Err(Error::NonContiguousNetmask { mask: mask.into() })
+    }
+}
+
+/// Combine a host destination with a separately-reported netmask.  The
+/// destination is returned unchanged if the mask doesn't fit it, but an
+/// unrecognized or non-contiguous mask is an error.
+pub(crate) fn with_netmask(
+    mut dest: Destination,
+    mask: Option<&str>,
+) -> Result<Destination, Error> {
+    if let (Entity::Cidr(cidr), Some(mask)) = (&dest.entity, mask) {
+        let len = parse_netmask(mask)?;
+        if let Some(addr) = cidr.first_address() {
+            if let Ok(network) = AnyIpCidr::new(addr, len) {
+                dest.entity = Entity::Cidr(network);
+            }
+        }
+    }
+    Ok(dest)
+}
+
+fn parse_flags(flags_s: &str, platform: NetstatPlatform) -> HashSet<RoutingFlag> {
+    flags_s
+        .chars()
+        .map(|letter| RoutingFlag::from_letter(letter, platform))
+        .collect()
 }
 
 fn parse_expire(s: &str) -> Result<Option<Duration>, Error> {
@@ -279,25 +480,51 @@ fn parse_expire(s: &str) -> Result<Option<Duration>, Error> {
     }
 }
 
-fn parse_ipv4dest(dest: &str) -> Result<Ipv4Addr, Error> {
-    dest.parse::<Ipv4Addr>().or_else(|_| {
-        let parts: Vec<u8> = dest
-            .split('.')
-            .map(str::parse)
-            .collect::<std::result::Result<Vec<u8>, std::num::ParseIntError>>()
+/// Parse a numeric column, where `-` means the value isn't available
+fn parse_counter<T: std::str::FromStr<Err = std::num::ParseIntError>>(
+    column: &'static str,
+    s: &str,
+) -> Result<Option<T>, Error> {
+    match s {
+        "-" => Ok(None),
+        n => n.parse().map(Some).map_err(|err| Error::ParseCounter {
+            column,
+            value: s.into(),
+            err,
+        }),
+    }
+}
+
+/// Parse an IPv4 destination.  netstat drops the trailing zero octets of
+/// networks that have their natural mask, e.g., `192.168.64` for
+/// 192.168.64.0/24, so a truncated address is a network masked to the octets
+/// present.
+fn parse_ipv4dest(dest: &str) -> Result<AnyIpCidr, Error> {
+    if let Ok(addr) = dest.parse::<Ipv4Addr>() {
+        return Ok(AnyIpCidr::new_host(IpAddr::V4(addr)));
+    }
+    for part in dest.split('.') {
+        part.parse::<u8>()
             .map_err(|err| Error::ParseIPv4AddrBadInt {
                 addr: dest.into(),
                 err,
             })?;
-        // This bizarre byte-ordering comes from inet_addr(3)
-        match parts.len() {
-            3 => Ok(Ipv4Addr::new(parts[0], parts[1], 0, parts[2])),
-            2 => Ok(Ipv4Addr::new(parts[0], 0, 0, parts[1])),
-            1 => Ok(Ipv4Addr::new(0, 0, 0, parts[0])),
-            len => Err(Error::ParseIPv4AddrNComps {
-                n_comps: len,
+    }
+    let len = match dest.split('.').count() {
+        1 => 8,
+        2 => 16,
+        3 => 24,
+        n_comps => {
+            return Err(Error::ParseIPv4AddrNComps {
+                n_comps,
                 addr: dest.into(),
-            }),
+            })
         }
-    })
+    };
+    format!("{dest}/{len}")
+        .parse()
+        .map_err(|err| Error::ParseDestination {
+            value: dest.into(),
+            err,
+        })
 }
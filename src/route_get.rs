@@ -0,0 +1,357 @@
+use crate::{
+    route_entry::{parse_destination, with_netmask},
+    Destination, LookupOptions, RoutingFlag, RoutingTable,
+};
+use std::{
+    collections::HashSet, net::IpAddr, process::ExitStatus, string::FromUtf8Error, time::Duration,
+};
+use tokio::process::Command;
+
+const ROUTE_PATH: &str = "/sbin/route";
+
+/// The kernel's answer to `route -n get <addr>`
+#[derive(Debug, Clone)]
+pub struct RouteGetReport {
+    /// The address that was looked up
+    pub route_to: IpAddr,
+
+    /// Zone of the address looked up, for link-local addresses (e.g., `en0`
+    /// in `fe80::1%en0`)
+    pub route_to_zone: Option<String>,
+
+    /// Destination of the matching route, combined with its mask
+    pub destination: Destination,
+
+    /// Gateway of the matching route.  Absent for directly-connected routes
+    pub gateway: Option<Destination>,
+
+    /// Interface the traffic leaves through
+    pub interface: Option<String>,
+
+    /// Routing flags
+    pub flags: HashSet<RoutingFlag>,
+
+    /// The metrics row, if present
+    pub metrics: Option<RouteMetrics>,
+}
+
+/// Per-route metrics reported by `route get`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouteMetrics {
+    pub recvpipe: u64,
+    pub sendpipe: u64,
+    pub ssthresh: u64,
+    /// Round trip time, in milliseconds
+    pub rtt_msec: u64,
+    /// Round trip time variance, in milliseconds
+    pub rttvar: u64,
+    pub hopcount: u64,
+    pub mtu: u64,
+    pub expire: Option<Duration>,
+}
+
+/// A disagreement between `route get` and `RoutingTable::find_route_entry`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteGetDisagreement {
+    /// The routing table has no route for the address
+    MissingRoute,
+    /// The tables picked routes with different destinations
+    Destination {
+        table: Destination,
+        kernel: Destination,
+    },
+    /// The tables route through different gateways
+    Gateway {
+        table: Destination,
+        kernel: Destination,
+    },
+    /// The tables route through different interfaces
+    Interface { table: String, kernel: String },
+}
+
+/// Various errors
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to execute {ROUTE_PATH}: {0}")]
+    RouteExec(std::io::Error),
+    #[error("route lookup failed: {0}")]
+    RouteFail(ExitStatus),
+    #[error("route output not UTF-8")]
+    RouteUtf8(FromUtf8Error),
+    #[error("missing {0:?} in route get output")]
+    MissingField(&'static str),
+    #[error("invalid lookup address {0:?}")]
+    BadAddress(String),
+    #[error("invalid metrics row {0:?}")]
+    BadMetrics(String),
+    #[error("parsing {field}: {err}")]
+    Parse {
+        field: &'static str,
+        err: crate::route_entry::Error,
+    },
+}
+
+impl RouteGetReport {
+    /// Parse the output of `route -n get <addr>` or `route -n get -inet6
+    /// <addr>`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a required field is missing or unparseable
+    pub fn parse(output: &str) -> Result<Self, Error> {
+        let mut route_to = None;
+        let mut route_to_zone = None;
+        let mut destination = None;
+        let mut mask = None;
+        let mut gateway = None;
+        let mut interface = None;
+        let mut flags = HashSet::new();
+        let mut metrics = None;
+
+        let mut lines = output.lines();
+        while let Some(line) = lines.next() {
+            if line.trim_start().starts_with("recvpipe") {
+                // The metrics header is followed by a single row of values
+                let row = lines.next().ok_or(Error::MissingField("metrics"))?;
+                metrics = Some(parse_metrics(row)?);
+                continue;
+            }
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "route to" => {
+                    let (addr, zone) = match value.split_once('%') {
+                        Some((addr, zone)) => (addr, Some(zone)),
+                        None => (value, None),
+                    };
+                    route_to = Some(
+                        addr.parse::<IpAddr>()
+                            .map_err(|_| Error::BadAddress(value.into()))?,
+                    );
+                    route_to_zone = zone.map(ToOwned::to_owned);
+                }
+                "destination" => destination = Some(parse_field("destination", value)?),
+                "mask" => mask = Some(value),
+                "gateway" => gateway = Some(parse_field("gateway", value)?),
+                "interface" => interface = Some(value.to_owned()),
+                "flags" => flags = RoutingFlag::parse_names(value),
+                _ => (),
+            }
+        }
+
+        let destination =
+            with_netmask(destination.ok_or(Error::MissingField("destination"))?, mask)
+                .map_err(|err| Error::Parse { field: "mask", err })?;
+
+        Ok(RouteGetReport {
+            route_to: route_to.ok_or(Error::MissingField("route to"))?,
+            route_to_zone,
+            destination,
+            gateway,
+            interface,
+            flags,
+            metrics,
+        })
+    }
+
+    /// Compare this report against the route `table` would choose for the same
+    /// address, returning every disagreement found.
+    #[must_use]
+    pub fn disagreements(&self, table: &RoutingTable) -> Vec<RouteGetDisagreement> {
+        // A zoned address can only leave through its zone's interface
+        let options = LookupOptions {
+            interface: self.route_to_zone.as_deref(),
+            ..LookupOptions::default()
+        };
+        let Some(route) = table.find_route_entry_with(self.route_to, &options) else {
+            return vec![RouteGetDisagreement::MissingRoute];
+        };
+        let mut disagreements = vec![];
+        if route.dest != self.destination {
+            disagreements.push(RouteGetDisagreement::Destination {
+                table: route.dest.clone(),
+                kernel: self.destination.clone(),
+            });
+        }
+        if let Some(gateway) = &self.gateway {
+            if route.gateway != *gateway {
+                disagreements.push(RouteGetDisagreement::Gateway {
+                    table: route.gateway.clone(),
+                    kernel: gateway.clone(),
+                });
+            }
+        }
+        if let Some(interface) = &self.interface {
+            if route.net_if != *interface {
+                disagreements.push(RouteGetDisagreement::Interface {
+                    table: route.net_if.clone(),
+                    kernel: interface.clone(),
+                });
+            }
+        }
+        disagreements
+    }
+}
+
+fn parse_field(field: &'static str, value: &str) -> Result<Destination, Error> {
+    parse_destination(value).map_err(|err| Error::Parse { field, err })
+}
+
+fn parse_metrics(row: &str) -> Result<RouteMetrics, Error> {
+    let values = row
+        .split_ascii_whitespace()
+        .map(str::parse)
+        .collect::<Result<Vec<u64>, _>>()
+        .map_err(|_| Error::BadMetrics(row.into()))?;
+    let [recvpipe, sendpipe, ssthresh, rtt_msec, rttvar, hopcount, mtu, expire] = values[..] else {
+        return Err(Error::BadMetrics(row.into()));
+    };
+    Ok(RouteMetrics {
+        recvpipe,
+        sendpipe,
+        ssthresh,
+        rtt_msec,
+        rttvar,
+        hopcount,
+        mtu,
+        expire: match expire {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        },
+    })
+}
+
+/// Ask the kernel which route it would use for `addr`, via `route -n get`
+///
+/// # Errors
+///
+/// Returns an error if command execution fails, or its output can't be parsed
+pub async fn route_get(addr: IpAddr) -> Result<RouteGetReport, Error> {
+    let mut command = Command::new(ROUTE_PATH);
+    command.args(["-n", "get"]);
+    if addr.is_ipv6() {
+        command.arg("-inet6");
+    }
+    let output = command
+        .arg(addr.to_string())
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await
+        .map_err(Error::RouteExec)?;
+    if !output.status.success() {
+        return Err(Error::RouteFail(output.status));
+    }
+    RouteGetReport::parse(&String::from_utf8(output.stdout).map_err(Error::RouteUtf8)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, RouteGetDisagreement, RouteGetReport};
+    use crate::{Entity, RoutingFlag, RoutingTable};
+    use std::time::Duration;
+
+    include!(concat!(env!("OUT_DIR"), "/sample_table.rs"));
+
+    #[test]
+    fn parse_default() {
+        let report = RouteGetReport::parse(ROUTE_GET_DEFAULT).expect("parse route get");
+        assert_eq!(
+            report.route_to,
+            "1.1.1.1".parse::<std::net::IpAddr>().unwrap()
+        );
+        assert!(matches!(report.destination.entity, Entity::Default));
+        assert_eq!(report.interface.as_deref(), Some("en0"));
+        assert!(report.flags.contains(&RoutingFlag::Gateway));
+        assert_eq!(report.metrics.as_ref().unwrap().mtu, 1500);
+
+        let table = RoutingTable::from_netstat_output(SAMPLE_TABLE).unwrap();
+        assert_eq!(report.disagreements(&table), vec![]);
+    }
+
+    #[test]
+    fn parse_inet6() {
+        let report = RouteGetReport::parse(ROUTE_GET_INET6).expect("parse route get");
+        assert_eq!(
+            report.gateway.as_ref().unwrap().zone.as_deref(),
+            Some("utun0")
+        );
+        assert_eq!(report.metrics.as_ref().unwrap().rtt_msec, 12);
+        assert_eq!(report.route_to_zone, None);
+
+        let table = RoutingTable::from_netstat_output(SAMPLE_TABLE).unwrap();
+        assert_eq!(report.disagreements(&table), vec![]);
+
+        // Link-local addresses are looked up with their zone
+        let report =
+            RouteGetReport::parse(&ROUTE_GET_INET6.replace("2606:4700:4700::1111", "fe80::1%en0"))
+                .expect("parse zoned route get");
+        assert_eq!(
+            report.route_to,
+            "fe80::1".parse::<std::net::IpAddr>().unwrap()
+        );
+        assert_eq!(report.route_to_zone.as_deref(), Some("en0"));
+
+        // Every interface has an `fe80::/64` route, but the zone picks en0's
+        let report = RouteGetReport::parse(
+            "   route to: fe80::1%en0
+destination: fe80::%en0
+       mask: ffff:ffff:ffff:ffff::
+  interface: en0
+      flags: <UP,DONE,CLONING,IFSCOPE>
+",
+        )
+        .expect("parse zoned route get");
+        assert_eq!(report.disagreements(&table), vec![]);
+    }
+
+    #[test]
+    fn parse_masked_and_connected() {
+        let report = RouteGetReport::parse(ROUTE_GET_SUBNET).expect("parse route get");
+        assert_eq!(report.destination.to_string(), "192.168.64.0/24");
+        assert!(report.gateway.is_none());
+
+        let report = RouteGetReport::parse(ROUTE_GET_CONNECTED).expect("parse route get");
+        assert_eq!(
+            report.metrics.unwrap().expire,
+            Some(Duration::from_secs(1181))
+        );
+    }
+
+    #[test]
+    fn disagreement() {
+        let table = RoutingTable::from_netstat_output(SAMPLE_TABLE).unwrap();
+        let report = RouteGetReport::parse(&ROUTE_GET_DEFAULT.replace("en0", "en7")).unwrap();
+        assert_eq!(
+            report.disagreements(&table),
+            vec![RouteGetDisagreement::Interface {
+                table: "en0".into(),
+                kernel: "en7".into()
+            }]
+        );
+
+        let table = RoutingTable::from_routes(vec![]);
+        assert_eq!(
+            report.disagreements(&table),
+            vec![RouteGetDisagreement::MissingRoute]
+        );
+    }
+
+    #[test]
+    fn bad_output() {
+        assert!(matches!(
+            RouteGetReport::parse("   route to: 1.1.1.1\n"),
+            Err(Error::MissingField("destination"))
+        ));
+        assert!(matches!(
+            RouteGetReport::parse("   route to: fe80::g%en0\n"),
+            Err(Error::BadAddress(addr)) if addr == "fe80::g%en0"
+        ));
+        let truncated = ROUTE_GET_DEFAULT.replace("      1500         0", "");
+        assert!(matches!(
+            RouteGetReport::parse(&truncated),
+            Err(Error::BadMetrics(_))
+        ));
+    }
+}
@@ -0,0 +1,195 @@
+use crate::{Entity, Protocol, RouteEntry};
+use cidr::AnyIpCidr;
+use std::{convert::TryFrom, net::IpAddr};
+
+/// Longest-prefix-match index over the entries of a routing table.
+///
+/// `Entity::Cidr` destinations are stored in one Patricia trie per address
+/// family, keyed by the destination network.  Default routes, which only match
+/// when no CIDR route does, are kept alongside in table order.  Link and MAC
+/// destinations never match an address lookup and aren't indexed at all.
+///
+/// The index stores positions into the routes slice it was built from, and
+/// must be rebuilt whenever that slice changes.
+#[derive(Debug, Default)]
+pub(crate) struct RouteIndex {
+    v4: Trie,
+    v6: Trie,
+    v4_default: Vec<usize>,
+    v6_default: Vec<usize>,
+    any: Vec<usize>,
+}
+
+impl RouteIndex {
+    pub(crate) fn new(routes: &[RouteEntry]) -> Self {
+        let mut index = RouteIndex::default();
+        for (i, route) in routes.iter().enumerate() {
+            match (&route.dest.entity, &route.gateway.entity) {
+                (Entity::Cidr(AnyIpCidr::V4(cidr)), _) => {
+                    index
+                        .v4
+                        .insert(v4_key(cidr.first_address()), cidr.network_length(), i);
+                }
+                (Entity::Cidr(AnyIpCidr::V6(cidr)), _) => {
+                    index
+                        .v6
+                        .insert(u128::from(cidr.first_address()), cidr.network_length(), i);
+                }
+                (Entity::Cidr(AnyIpCidr::Any), _) => index.any.push(i),
+                // Default routes only apply when they point at an address
+                (Entity::Default, Entity::Cidr(_)) => match route.proto {
+                    Protocol::V4 => index.v4_default.push(i),
+                    Protocol::V6 => index.v6_default.push(i),
+                },
+                _ => (),
+            }
+        }
+        index
+    }
+
+    /// Return the positions of the equally-precise best matches for `addr`, in
+    /// table order.
+    ///
+    /// The ranking mirrors `RouteEntry::most_precise`: the longest matching
+    /// CIDR wins, then the last `Any` CIDR, then the default routes.
+    pub(crate) fn candidates(&self, addr: IpAddr) -> &[usize] {
+        let (trie, key, defaults) = match addr {
+            IpAddr::V4(addr) => (&self.v4, v4_key(addr), &self.v4_default),
+            IpAddr::V6(addr) => (&self.v6, u128::from(addr), &self.v6_default),
+        };
+        if let Some(routes) = trie.lookup(key, |_| true) {
+            routes
+        } else if let Some(last) = self.any.len().checked_sub(1) {
+            &self.any[last..]
+        } else {
+            defaults
+        }
+    }
+
+    /// Like `candidates`, but only considering the routes at the positions
+    /// `include` accepts
+    pub(crate) fn candidates_where(
+        &self,
+        addr: IpAddr,
+        include: impl Fn(usize) -> bool,
+    ) -> Vec<usize> {
+        let (trie, key, defaults) = match addr {
+            IpAddr::V4(addr) => (&self.v4, v4_key(addr), &self.v4_default),
+            IpAddr::V6(addr) => (&self.v6, u128::from(addr), &self.v6_default),
+        };
+        let routes = if let Some(routes) = trie.lookup(key, &include) {
+            routes
+        } else if let Some(last) = self.any.iter().rposition(|&i| include(i)) {
+            &self.any[last..=last]
+        } else {
+            defaults
+        };
+        routes.iter().copied().filter(|&i| include(i)).collect()
+    }
+}
+
+fn v4_key(addr: std::net::Ipv4Addr) -> u128 {
+    u128::from(u32::from(addr)) << 96
+}
+
+/// A path-compressed binary trie of network prefixes.  Keys are left-aligned
+/// in a `u128` so both address families share the implementation.
+#[derive(Debug, Default)]
+struct Trie {
+    root: Option<Box<Node>>,
+}
+
+#[derive(Debug)]
+struct Node {
+    key: u128,
+    len: u8,
+    /// Routes whose destination is exactly this prefix, in table order
+    routes: Vec<usize>,
+    children: [Option<Box<Node>>; 2],
+}
+
+impl Node {
+    fn new(key: u128, len: u8) -> Self {
+        Node {
+            key: mask(key, len),
+            len,
+            routes: vec![],
+            children: [None, None],
+        }
+    }
+}
+
+impl Trie {
+    fn insert(&mut self, key: u128, len: u8, route: usize) {
+        insert_at(&mut self.root, key, len, route);
+    }
+
+    /// Find the routes of the longest prefix matching `key` that has any
+    /// route `include` accepts
+    fn lookup(&self, key: u128, include: impl Fn(usize) -> bool) -> Option<&[usize]> {
+        let mut best = None;
+        let mut cur = self.root.as_deref();
+        while let Some(node) = cur {
+            if mask(key, node.len) != node.key {
+                break;
+            }
+            if node.routes.iter().any(|&i| include(i)) {
+                best = Some(node.routes.as_slice());
+            }
+            if node.len == 128 {
+                break;
+            }
+            cur = node.children[bit_at(key, node.len)].as_deref();
+        }
+        best
+    }
+}
+
+fn insert_at(slot: &mut Option<Box<Node>>, key: u128, len: u8, route: usize) {
+    let Some(node) = slot else {
+        let mut leaf = Node::new(key, len);
+        leaf.routes.push(route);
+        *slot = Some(Box::new(leaf));
+        return;
+    };
+    let common = common_prefix_len(node.key, node.len, key, len);
+    if common == node.len {
+        if len == node.len {
+            node.routes.push(route);
+        } else {
+            insert_at(&mut node.children[bit_at(key, node.len)], key, len, route);
+        }
+        return;
+    }
+
+    // The new prefix diverges partway through this node: split it.
+    let old = slot.take().unwrap_or_else(|| unreachable!());
+    let mut parent = Node::new(key, common);
+    let old_bit = bit_at(old.key, common);
+    parent.children[old_bit] = Some(old);
+    if len == common {
+        parent.routes.push(route);
+    } else {
+        let mut leaf = Node::new(key, len);
+        leaf.routes.push(route);
+        parent.children[1 - old_bit] = Some(Box::new(leaf));
+    }
+    *slot = Some(Box::new(parent));
+}
+
+fn mask(key: u128, len: u8) -> u128 {
+    match len {
+        0 => 0,
+        len => key & (u128::MAX << (128 - u32::from(len))),
+    }
+}
+
+fn bit_at(key: u128, pos: u8) -> usize {
+    usize::from((key >> (127 - u32::from(pos))) & 1 == 1)
+}
+
+fn common_prefix_len(a: u128, a_len: u8, b: u128, b_len: u8) -> u8 {
+    let diff = (a ^ b).leading_zeros();
+    // Both lengths are at most 128, so the result always fits
+    u8::try_from(diff.min(u32::from(a_len.min(b_len)))).unwrap_or(128)
+}
@@ -0,0 +1,400 @@
+use crate::RoutingFlag;
+use futures::Stream;
+use std::collections::HashSet;
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, BufReader, Lines},
+    process::{Child, Command},
+};
+
+const ROUTE_PATH: &str = "/sbin/route";
+
+/// A routing socket message, as printed by `route -n monitor`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteEvent {
+    /// `RTM_ADD`: a route was added
+    Add(RouteMessage),
+    /// `RTM_DELETE`: a route was deleted
+    Delete(RouteMessage),
+    /// `RTM_CHANGE`: a route's gateway, metrics or flags changed
+    Change(RouteMessage),
+    /// `RTM_GET`: a route was looked up
+    Get(RouteMessage),
+    /// `RTM_LOSING`: the kernel suspects the route is failing
+    Losing(RouteMessage),
+    /// `RTM_REDIRECT`: the kernel was told to use a different route
+    Redirect(RouteMessage),
+    /// `RTM_MISS`: a lookup failed
+    Miss(RouteMessage),
+    /// `RTM_LOCK`: route metrics were locked
+    Lock(RouteMessage),
+    /// `RTM_RESOLVE`: a route was created by cloning
+    Resolve(RouteMessage),
+    /// `RTM_NEWADDR`: an address was added to an interface
+    NewAddr(RouteMessage),
+    /// `RTM_DELADDR`: an address was removed from an interface
+    DelAddr(RouteMessage),
+    /// `RTM_IFINFO`: an interface changed status
+    IfInfo(RouteMessage),
+    /// `RTM_NEWMADDR`: an interface joined a multicast group
+    NewMAddr(RouteMessage),
+    /// `RTM_DELMADDR`: an interface left a multicast group
+    DelMAddr(RouteMessage),
+    /// Any other message type, such as `RTM_IFINFO2`
+    Other { kind: String, message: RouteMessage },
+}
+
+/// The body of a routing socket message
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouteMessage {
+    pub pid: Option<i32>,
+    pub seq: Option<i32>,
+    pub errno: Option<i32>,
+    /// Interface scope of the route
+    pub ifscope: Option<u32>,
+    /// Interface index (`RTM_IFINFO`)
+    pub if_index: Option<u32>,
+    /// Address metric (`RTM_NEWADDR`/`RTM_DELADDR`)
+    pub metric: Option<i32>,
+    /// Route or interface flags
+    pub flags: HashSet<RoutingFlag>,
+    /// The socket addresses carried by the message
+    pub addrs: SockAddrs,
+}
+
+/// Socket addresses attached to a routing message, as printed (numerically)
+/// by `route -n monitor`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SockAddrs {
+    pub dst: Option<String>,
+    pub gateway: Option<String>,
+    pub netmask: Option<String>,
+    pub genmask: Option<String>,
+    pub ifp: Option<String>,
+    pub ifa: Option<String>,
+    pub author: Option<String>,
+    pub brd: Option<String>,
+}
+
+/// Various errors
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to execute {ROUTE_PATH}: {0}")]
+    MonitorExec(std::io::Error),
+    #[error("reading route monitor output: {0}")]
+    MonitorRead(std::io::Error),
+    #[error("invalid {field} {value:?} in route message header")]
+    BadHeaderField { field: String, value: String },
+    #[error("socket addresses {kinds:?} don't match values {values:?}")]
+    SockAddrMismatch { kinds: String, values: String },
+    #[error("unknown socket address type {0:?}")]
+    UnknownSockAddr(String),
+}
+
+impl RouteEvent {
+    /// The message carried by the event
+    #[must_use]
+    pub fn message(&self) -> &RouteMessage {
+        match self {
+            RouteEvent::Add(message)
+            | RouteEvent::Delete(message)
+            | RouteEvent::Change(message)
+            | RouteEvent::Get(message)
+            | RouteEvent::Losing(message)
+            | RouteEvent::Redirect(message)
+            | RouteEvent::Miss(message)
+            | RouteEvent::Lock(message)
+            | RouteEvent::Resolve(message)
+            | RouteEvent::NewAddr(message)
+            | RouteEvent::DelAddr(message)
+            | RouteEvent::IfInfo(message)
+            | RouteEvent::NewMAddr(message)
+            | RouteEvent::DelMAddr(message)
+            | RouteEvent::Other { message, .. } => message,
+        }
+    }
+
+    fn message_mut(&mut self) -> &mut RouteMessage {
+        match self {
+            RouteEvent::Add(message)
+            | RouteEvent::Delete(message)
+            | RouteEvent::Change(message)
+            | RouteEvent::Get(message)
+            | RouteEvent::Losing(message)
+            | RouteEvent::Redirect(message)
+            | RouteEvent::Miss(message)
+            | RouteEvent::Lock(message)
+            | RouteEvent::Resolve(message)
+            | RouteEvent::NewAddr(message)
+            | RouteEvent::DelAddr(message)
+            | RouteEvent::IfInfo(message)
+            | RouteEvent::NewMAddr(message)
+            | RouteEvent::DelMAddr(message)
+            | RouteEvent::Other { message, .. } => message,
+        }
+    }
+
+    /// Parse a message header line, such as
+    /// `RTM_ADD: Add Route: len 148, pid: 4211, seq 1, errno 0, flags:<UP>`
+    fn parse_header(line: &str) -> Result<Self, Error> {
+        let (kind, rest) = line.split_once(':').unwrap_or((line, ""));
+        // Skip the human-readable description
+        let rest = rest.split_once(':').map_or(rest, |(_, rest)| rest);
+        let (fields, flags) = match rest.split_once("flags:") {
+            Some((fields, flags)) => (fields, RoutingFlag::parse_names(flags)),
+            None => (rest, HashSet::new()),
+        };
+
+        let mut message = RouteMessage {
+            flags,
+            ..RouteMessage::default()
+        };
+        for field in fields.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            let (name, value) = field.rsplit_once(' ').unwrap_or((field, ""));
+            let name = name.trim_end_matches(':');
+            match name {
+                "pid" => message.pid = Some(parse_field(name, value)?),
+                "seq" => message.seq = Some(parse_field(name, value)?),
+                "errno" => message.errno = Some(parse_field(name, value)?),
+                "ifscope" => message.ifscope = Some(parse_field(name, value)?),
+                "if#" => message.if_index = Some(parse_field(name, value)?),
+                "metric" => message.metric = Some(parse_field(name, value)?),
+                // Includes `len`, the message size
+                _ => (),
+            }
+        }
+
+        Ok(match kind {
+            "RTM_ADD" => RouteEvent::Add(message),
+            "RTM_DELETE" => RouteEvent::Delete(message),
+            "RTM_CHANGE" => RouteEvent::Change(message),
+            "RTM_GET" => RouteEvent::Get(message),
+            "RTM_LOSING" => RouteEvent::Losing(message),
+            "RTM_REDIRECT" => RouteEvent::Redirect(message),
+            "RTM_MISS" => RouteEvent::Miss(message),
+            "RTM_LOCK" => RouteEvent::Lock(message),
+            "RTM_RESOLVE" => RouteEvent::Resolve(message),
+            "RTM_NEWADDR" => RouteEvent::NewAddr(message),
+            "RTM_DELADDR" => RouteEvent::DelAddr(message),
+            "RTM_IFINFO" => RouteEvent::IfInfo(message),
+            "RTM_NEWMADDR" => RouteEvent::NewMAddr(message),
+            "RTM_DELMADDR" => RouteEvent::DelMAddr(message),
+            kind => RouteEvent::Other {
+                kind: kind.into(),
+                message,
+            },
+        })
+    }
+}
+
+fn parse_field<T: std::str::FromStr>(field: &str, value: &str) -> Result<T, Error> {
+    value.parse().map_err(|_| Error::BadHeaderField {
+        field: field.into(),
+        value: value.into(),
+    })
+}
+
+impl SockAddrs {
+    /// Pair the `<DST,GATEWAY,...>` list with the line of values following it
+    fn parse(kinds: &str, values: &str) -> Result<Self, Error> {
+        let kind_list: Vec<&str> = kinds
+            .trim()
+            .trim_start_matches('<')
+            .trim_end_matches('>')
+            .split(',')
+            .filter(|kind| !kind.is_empty())
+            .collect();
+        let value_list: Vec<&str> = values.split_ascii_whitespace().collect();
+        if kind_list.len() != value_list.len() {
+            return Err(Error::SockAddrMismatch {
+                kinds: kinds.into(),
+                values: values.into(),
+            });
+        }
+
+        let mut addrs = SockAddrs::default();
+        for (kind, value) in kind_list.into_iter().zip(value_list) {
+            let slot = match kind {
+                "DST" => &mut addrs.dst,
+                "GATEWAY" => &mut addrs.gateway,
+                "NETMASK" => &mut addrs.netmask,
+                "GENMASK" => &mut addrs.genmask,
+                "IFP" => &mut addrs.ifp,
+                "IFA" => &mut addrs.ifa,
+                "AUTHOR" => &mut addrs.author,
+                "BRD" => &mut addrs.brd,
+                kind => return Err(Error::UnknownSockAddr(kind.into())),
+            };
+            *slot = Some(value.to_owned());
+        }
+        Ok(addrs)
+    }
+}
+
+/// Incremental parser state for `route -n monitor` output
+struct EventReader<R> {
+    lines: Lines<R>,
+    /// The event whose lines are being read
+    pending: Option<RouteEvent>,
+    /// The `sockaddrs: <...>` list awaiting its line of values
+    sockaddrs: Option<String>,
+    done: bool,
+    /// Keeps the `route` process alive for as long as it's being read
+    _child: Option<Child>,
+}
+
+impl<R: AsyncBufRead + Unpin> EventReader<R> {
+    async fn next_event(&mut self) -> Option<Result<RouteEvent, Error>> {
+        while !self.done {
+            let line = match self.lines.next_line().await {
+                Ok(Some(line)) => line,
+                Ok(None) => {
+                    self.done = true;
+                    break;
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(Error::MonitorRead(err)));
+                }
+            };
+            if let Some(event) = self.parse_line(&line).transpose() {
+                return Some(event);
+            }
+        }
+        self.pending.take().map(Ok)
+    }
+
+    /// Feed a line to the parser, returning an event once one is complete
+    fn parse_line(&mut self, line: &str) -> Result<Option<RouteEvent>, Error> {
+        if let Some(kinds) = self.sockaddrs.take() {
+            if let Some(event) = &mut self.pending {
+                match SockAddrs::parse(&kinds, line) {
+                    Ok(addrs) => event.message_mut().addrs = addrs,
+                    Err(err) => {
+                        // Don't report the event later without its addresses
+                        self.pending = None;
+                        return Err(err);
+                    }
+                }
+            }
+            return Ok(None);
+        }
+        if line.starts_with("RTM_") {
+            let previous = self.pending.replace(RouteEvent::parse_header(line)?);
+            return Ok(previous);
+        }
+        if let Some(kinds) = line.strip_prefix("sockaddrs:") {
+            self.sockaddrs = Some(kinds.to_owned());
+            return Ok(None);
+        }
+        if line.trim().is_empty() || line.starts_with("got message") {
+            return Ok(self.pending.take());
+        }
+        // Anything else (e.g., `locks:  inits:`) isn't of interest
+        Ok(None)
+    }
+}
+
+fn event_stream<R: AsyncBufRead + Unpin>(
+    reader: R,
+    child: Option<Child>,
+) -> impl Stream<Item = Result<RouteEvent, Error>> {
+    let reader = EventReader {
+        lines: reader.lines(),
+        pending: None,
+        sockaddrs: None,
+        done: false,
+        _child: child,
+    };
+    futures::stream::unfold(reader, |mut reader| async move {
+        reader.next_event().await.map(|event| (event, reader))
+    })
+}
+
+/// Parse `route -n monitor` output from any buffered reader into a stream of
+/// events
+pub fn parse_route_events<R: AsyncBufRead + Unpin>(
+    reader: R,
+) -> impl Stream<Item = Result<RouteEvent, Error>> {
+    event_stream(reader, None)
+}
+
+/// Run `route -n monitor` and stream the events it reports.  The process is
+/// killed when the stream is dropped.
+///
+/// # Errors
+///
+/// Returns an error if the `route` command fails to execute
+pub fn route_monitor() -> Result<impl Stream<Item = Result<RouteEvent, Error>>, Error> {
+    let mut child = Command::new(ROUTE_PATH)
+        .args(["-n", "monitor"])
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(Error::MonitorExec)?;
+    let stdout = child.stdout.take().ok_or_else(|| {
+        Error::MonitorExec(std::io::Error::new(
+            std::io::ErrorKind::BrokenPipe,
+            "no stdout",
+        ))
+    })?;
+    Ok(event_stream(BufReader::new(stdout), Some(child)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_route_events, Error, RouteEvent};
+    use crate::RoutingFlag;
+    use futures::StreamExt;
+
+    include!(concat!(env!("OUT_DIR"), "/sample_table.rs"));
+
+    #[tokio::test]
+    async fn recorded_events() {
+        let events: Vec<RouteEvent> = parse_route_events(ROUTE_MONITOR.as_bytes())
+            .map(|event| event.expect("parse route event"))
+            .collect()
+            .await;
+        assert_eq!(events.len(), 7);
+
+        let RouteEvent::Add(add) = &events[0] else {
+            panic!("expected RTM_ADD, got {:?}", events[0]);
+        };
+        assert_eq!(add.pid, Some(4211));
+        assert!(add.flags.contains(&RoutingFlag::Gateway));
+        assert_eq!(add.addrs.dst.as_deref(), Some("10.1.0.0"));
+        assert_eq!(add.addrs.gateway.as_deref(), Some("192.168.64.1"));
+        assert_eq!(add.addrs.netmask.as_deref(), Some("255.255.0.0"));
+
+        assert!(matches!(&events[1], RouteEvent::Resolve(m) if m.ifscope == Some(5)));
+        assert!(matches!(&events[2], RouteEvent::Change(_)));
+        assert!(matches!(&events[3], RouteEvent::Delete(_)));
+        assert!(
+            matches!(&events[4], RouteEvent::NewAddr(m) if m.addrs.ifa.as_deref() == Some("192.168.64.23"))
+        );
+        assert!(matches!(&events[5], RouteEvent::IfInfo(m) if m.if_index == Some(5)));
+        assert!(matches!(&events[6], RouteEvent::Delete(m) if m.seq == Some(3)));
+    }
+
+    #[tokio::test]
+    async fn unterminated_and_unknown() {
+        let input = "RTM_IFINFO2: iface status change: len 112, if# 7, flags:<UP>";
+        let events: Vec<_> = parse_route_events(input.as_bytes()).collect().await;
+        assert!(matches!(
+            &events[..],
+            [Ok(RouteEvent::Other { kind, message })] if kind == "RTM_IFINFO2" && message.if_index == Some(7)
+        ));
+    }
+
+    #[tokio::test]
+    async fn bad_messages() {
+        let input = "RTM_ADD: Add Route: len 148, pid: x, seq 1, errno 0, flags:<UP>\n";
+        let events: Vec<_> = parse_route_events(input.as_bytes()).collect().await;
+        assert!(matches!(&events[..], [Err(Error::BadHeaderField { .. })]));
+
+        let input =
+            "RTM_ADD: Add Route: len 148, flags:<UP>\nsockaddrs: <DST,GATEWAY>\n 10.0.0.0\n";
+        let events: Vec<_> = parse_route_events(input.as_bytes()).collect().await;
+        assert!(matches!(&events[..], [Err(Error::SockAddrMismatch { .. })]));
+    }
+}
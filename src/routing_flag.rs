@@ -1,3 +1,6 @@
+use crate::NetstatPlatform;
+use std::collections::HashSet;
+
 #[allow(dead_code)]
 #[derive(Clone, Debug, std::hash::Hash, Eq, PartialEq)]
 pub enum RoutingFlag {
@@ -24,6 +27,14 @@ pub enum RoutingFlag {
     XResolve,  // X
     Proxy,     // Y
     Global,    // g
+    Done,      // d (OpenBSD)
+    Cached,    // h (OpenBSD)
+    Local,     // l (OpenBSD)
+    Connected, // n (OpenBSD)
+    Multipath, // P (OpenBSD)
+    Mpls,      // T (OpenBSD)
+    Bfd,       // F (OpenBSD)
+    Kernel,    // K (NetBSD, DragonFly)
     Unknown,
 }
 
@@ -57,3 +68,88 @@ impl From<char> for RoutingFlag {
         }
     }
 }
+
+impl RoutingFlag {
+    /// Map a flag letter printed by `netstat -r` on `platform` to a
+    /// `RoutingFlag`.  Letters that only another platform uses are unknown.
+    #[must_use]
+    pub fn from_letter(letter: char, platform: NetstatPlatform) -> Self {
+        match (platform, letter) {
+            // Darwin-only flags
+            (NetstatPlatform::FreeBsd, 'I' | 'i' | 'm' | 'r' | 'g' | 'Y')
+            | (NetstatPlatform::OpenBsd, 'I' | 'i' | 'r' | 'g' | 'Y' | 'W' | 'X')
+            | (NetstatPlatform::NetBsd | NetstatPlatform::DragonFly, 'I' | 'i' | 'r' | 'g' | 'Y') => {
+                RoutingFlag::Unknown
+            }
+            // Flags of the other BSDs, and `c` for a cloned route rather than
+            // Darwin's protocol-specific cloning
+            (NetstatPlatform::OpenBsd | NetstatPlatform::NetBsd, 'c') => RoutingFlag::WasCloned,
+            (NetstatPlatform::OpenBsd | NetstatPlatform::NetBsd, 'd') => RoutingFlag::Done,
+            (
+                NetstatPlatform::OpenBsd | NetstatPlatform::NetBsd | NetstatPlatform::DragonFly,
+                'l',
+            ) => RoutingFlag::Local,
+            (NetstatPlatform::NetBsd | NetstatPlatform::DragonFly, 'p') => RoutingFlag::Proto3,
+            (NetstatPlatform::NetBsd | NetstatPlatform::DragonFly, 'K') => RoutingFlag::Kernel,
+            (NetstatPlatform::OpenBsd, 'h') => RoutingFlag::Cached,
+            (NetstatPlatform::OpenBsd, 'n') => RoutingFlag::Connected,
+            (NetstatPlatform::OpenBsd, 'P') => RoutingFlag::Multipath,
+            (NetstatPlatform::OpenBsd, 'T') => RoutingFlag::Mpls,
+            (NetstatPlatform::OpenBsd, 'F') => RoutingFlag::Bfd,
+            _ => RoutingFlag::from(letter),
+        }
+    }
+
+    /// Map a flag name, as printed between angle brackets by `route get` and
+    /// `route monitor` (e.g., `<UP,GATEWAY,STATIC>`), to a `RoutingFlag`.
+    #[must_use]
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "PROTO1" => RoutingFlag::Proto1,
+            "PROTO2" => RoutingFlag::Proto2,
+            "PROTO3" => RoutingFlag::Proto3,
+            "BLACKHOLE" => RoutingFlag::Blackhole,
+            "BROADCAST" => RoutingFlag::Broadcast,
+            "CLONING" => RoutingFlag::Cloning,
+            "PRCLONING" => RoutingFlag::PrCloning,
+            "DYNAMIC" => RoutingFlag::Dynamic,
+            "GATEWAY" => RoutingFlag::Gateway,
+            "HOST" => RoutingFlag::Host,
+            "IFSCOPE" => RoutingFlag::IfScope,
+            "IFREF" => RoutingFlag::IfRef,
+            "LLINFO" => RoutingFlag::LlInfo,
+            "MODIFIED" => RoutingFlag::Modified,
+            "MULTICAST" => RoutingFlag::Multicast,
+            "REJECT" => RoutingFlag::Reject,
+            "ROUTER" => RoutingFlag::Router,
+            "STATIC" => RoutingFlag::Static,
+            "UP" => RoutingFlag::Up,
+            "WASCLONED" => RoutingFlag::WasCloned,
+            "XRESOLVE" => RoutingFlag::XResolve,
+            "PROXY" => RoutingFlag::Proxy,
+            "GLOBAL" => RoutingFlag::Global,
+            "DONE" => RoutingFlag::Done,
+            "CACHED" => RoutingFlag::Cached,
+            "LOCAL" => RoutingFlag::Local,
+            "CONNECTED" => RoutingFlag::Connected,
+            "MPATH" => RoutingFlag::Multipath,
+            "MPLS" => RoutingFlag::Mpls,
+            "BFD" => RoutingFlag::Bfd,
+            "KERNEL" => RoutingFlag::Kernel,
+            _ => RoutingFlag::Unknown,
+        }
+    }
+
+    /// Parse a bracketed, comma-separated list of flag names, such as
+    /// `<UP,GATEWAY,DONE,STATIC>`.
+    pub(crate) fn parse_names(names: &str) -> HashSet<Self> {
+        names
+            .trim()
+            .trim_start_matches('<')
+            .trim_end_matches('>')
+            .split(',')
+            .filter(|name| !name.is_empty())
+            .map(RoutingFlag::from_name)
+            .collect()
+    }
+}
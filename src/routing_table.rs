@@ -1,8 +1,50 @@
-use crate::{Entity, Protocol, RouteEntry};
-use std::{collections::HashMap, net::IpAddr, process::ExitStatus, string::FromUtf8Error};
+use crate::{
+    columns::Columns, route_index::RouteIndex, Entity, NetstatDialect, NetstatPlatform,
+    NetworkInterfaceOrder, Protocol, RouteEntry, RoutingFlag,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::IpAddr,
+    process::ExitStatus,
+    string::FromUtf8Error,
+};
 use tokio::process::Command;
 
-const NETSTAT_PATH: &str = "/usr/sbin/netstat";
+/// Options controlling how `netstat` is invoked
+#[derive(Debug, Clone, Default)]
+pub struct NetstatOptions {
+    /// The system to run `netstat` for, which determines its path and flags
+    pub platform: NetstatPlatform,
+
+    /// Request the extended listing, which adds the Refs (Darwin `-l` only),
+    /// Use and Mtu columns.  On FreeBSD this is the wide listing, and OpenBSD
+    /// always prints them.
+    pub extended: bool,
+
+    /// Request the wide (`-W`) listing, which doesn't truncate addresses
+    pub wide: bool,
+
+    /// Request protocol-cloned routes too (Darwin `-a` only)
+    pub all: bool,
+
+    /// Read the routing table of this FIB (FreeBSD `-F`) or routing table
+    /// (OpenBSD `-T`) instead of the default one
+    pub fib: Option<u32>,
+}
+
+/// Options controlling how routes are looked up
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LookupOptions<'a> {
+    /// Break ties between equally-precise routes by preferring the interface
+    /// ranked higher in the system's ordering.  Without it, the route listed
+    /// first wins.
+    pub interface_order: Option<&'a NetworkInterfaceOrder>,
+
+    /// Scope the lookup to an interface, as for a socket bound to it (e.g.,
+    /// the queries of a scoped resolver).  Only routes on the interface are
+    /// considered.
+    pub interface: Option<&'a str>,
+}
 
 /// A snapshot of the routing table
 #[derive(Debug)]
@@ -10,13 +52,22 @@ pub struct RoutingTable {
     routes: Vec<RouteEntry>,
     /// Map of interfaces to their default routers
     if_router: HashMap<String, Vec<IpAddr>>,
+    /// Longest-prefix-match index over `routes`
+    index: RouteIndex,
+    /// The netstat output variant the table was parsed from
+    dialect: NetstatDialect,
+    /// The FIB or routing domain the table was read from
+    table_id: Option<u32>,
 }
 
 /// Various errors
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
-    #[error("failed to execute {NETSTAT_PATH}: {0}")]
-    NetstatExec(std::io::Error),
+    #[error("failed to execute {path}: {err}")]
+    NetstatExec {
+        path: &'static str,
+        err: std::io::Error,
+    },
     #[error("failed to get routing table: {0}")]
     NetstatFail(ExitStatus),
     #[error("netstat output not non-UTF-8")]
@@ -37,85 +88,318 @@ impl RoutingTable {
     /// Returns an error if the `netstat` command fails to execute, or returns
     /// unparseable output.
     pub async fn load_from_netstat() -> Result<Self, Error> {
-        let output = execute_netstat().await?;
-        Self::from_netstat_output(&output)
+        Self::load_from_netstat_with(&NetstatOptions::default()).await
+    }
+
+    /// Query the routing table using FreeBSD's `netstat` command.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `netstat` command fails to execute, or returns
+    /// unparseable output.
+    pub async fn load_from_freebsd_netstat() -> Result<Self, Error> {
+        Self::load_from_netstat_with(&NetstatOptions {
+            platform: NetstatPlatform::FreeBsd,
+            ..NetstatOptions::default()
+        })
+        .await
+    }
+
+    /// Query the routing table using the `netstat` command, invoked according
+    /// to `options`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `netstat` command fails to execute, or returns
+    /// unparseable output.
+    pub async fn load_from_netstat_with(options: &NetstatOptions) -> Result<Self, Error> {
+        let output = execute_netstat_with(options).await?;
+        let mut table = Self::from_netstat_output_with(&output, options.platform)?;
+        table.dialect.all = options.all && options.platform == NetstatPlatform::Darwin;
+        Ok(match options.fib {
+            Some(fib) => table.with_table_id(fib),
+            None => table,
+        })
     }
 
     /// Generate a `RoutingTable` from complete netstat output.  The output should
-    /// conform to what would be returned from `netstat -rn` on macOS/Darwin.
+    /// conform to what would be returned from `netstat -rn` on macOS/Darwin,
+    /// optionally with `-l`, `-a` or `-W`; the variant (short of `-a`) is
+    /// detected from the output and available from
+    /// [`RoutingTable::dialect`].  A FIB named in the title (e.g.,
+    /// `Routing tables (fib: 1)`) becomes the table ID.  To find tables
+    /// embedded in a larger text, use [`RoutingTable::scan_text`].
     ///
     /// # Errors
     ///
     /// Returns an error
     pub fn from_netstat_output(output: &str) -> Result<RoutingTable, Error> {
+        Self::from_netstat_output_with(output, NetstatPlatform::default())
+    }
+
+    /// Generate a `RoutingTable` from complete netstat output printed on
+    /// `platform`, whose flag letters it uses.  The output of the other BSDs
+    /// is recognized by its columns even when Darwin is given.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error
+    pub fn from_netstat_output_with(
+        output: &str,
+        platform: NetstatPlatform,
+    ) -> Result<RoutingTable, Error> {
         let mut lines = output.lines();
-        let mut headers = vec![];
-        let mut routes = vec![];
-        let mut proto = None;
-        let mut if_router = HashMap::new();
+        let mut parser = NetstatParser::new(platform);
 
         while let Some(line) = lines.next() {
-            if line.is_empty() || line.starts_with("Routing table") {
+            if line.is_empty() {
                 continue;
             }
-            match line {
-                section @ ("Internet:" | "Internet6:") => {
-                    proto = match section {
-                        "Internet:" => Some(Protocol::V4),
-                        "Internet6:" => Some(Protocol::V6),
-                        _ => unreachable!(),
-                    };
-                    // Next line will contain the column headers
-                    if let Some(line) = lines.next() {
-                        headers = line.split_ascii_whitespace().collect();
-                    } else {
-                        return Err(Error::NetstatParseNoHeaders(section.into()));
-                    }
-                    continue;
-                }
-                entry => {
-                    if let Some(proto) = proto {
-                        let route = RouteEntry::parse(proto, entry, &headers)?;
-                        if let (Entity::Default, Entity::Cidr(cidr)) =
-                            (&route.dest.entity, &route.gateway.entity)
-                        {
-                            if cidr.is_host_address() {
-                                let route = route.clone();
-                                let gws = if_router.entry(route.net_if).or_insert_with(Vec::new);
-                                // The route parser doesn't produce `Any` CIDRs,
-                                // so there's always a first address.
-                                gws.push(cidr.first_address().unwrap_or_else(|| unreachable!()));
-                            }
-                        }
-                        routes.push(route);
-                    } else {
-                        return Err(Error::EntryBeforeProto);
-                    }
+            if line.starts_with("Routing table") {
+                parser.title(line);
+            } else if let Some(proto) = NetstatParser::section_proto(line) {
+                // Next line will contain the column headers
+                let header = lines
+                    .next()
+                    .ok_or_else(|| Error::NetstatParseNoHeaders(line.into()))?;
+                parser.section(proto, header);
+            } else {
+                parser.entry(line)?;
+            }
+        }
+        Ok(parser.finish())
+    }
+
+    /// Generate a `RoutingTable` from already-parsed route entries, indexing
+    /// them for lookups.
+    #[must_use]
+    pub fn from_routes(routes: Vec<RouteEntry>) -> RoutingTable {
+        let mut if_router = HashMap::new();
+        for route in &routes {
+            if let (Entity::Default, Entity::Cidr(cidr)) =
+                (&route.dest.entity, &route.gateway.entity)
+            {
+                if cidr.is_host_address() {
+                    let gws = if_router
+                        .entry(route.net_if.clone())
+                        .or_insert_with(Vec::new);
+                    // The route parser doesn't produce `Any` CIDRs,
+                    // so there's always a first address.
+                    gws.push(cidr.first_address().unwrap_or_else(|| unreachable!()));
                 }
-            };
+            }
+        }
+        let index = RouteIndex::new(&routes);
+        RoutingTable {
+            routes,
+            if_router,
+            index,
+            dialect: NetstatDialect::default(),
+            table_id: None,
         }
-        Ok(RoutingTable { routes, if_router })
+    }
+
+    /// Record the netstat output variant the table's routes came from
+    #[must_use]
+    pub fn with_dialect(mut self, dialect: NetstatDialect) -> RoutingTable {
+        self.dialect = dialect;
+        self
+    }
+
+    /// The netstat output variant the table was parsed from
+    #[must_use]
+    pub fn dialect(&self) -> NetstatDialect {
+        self.dialect
+    }
+
+    /// Record the FIB or routing domain the table's routes belong to
+    #[must_use]
+    pub fn with_table_id(mut self, table_id: u32) -> RoutingTable {
+        self.table_id = Some(table_id);
+        self
+    }
+
+    /// The FIB (FreeBSD) or routing table (OpenBSD) the table was read from,
+    /// if known
+    #[must_use]
+    pub fn table_id(&self) -> Option<u32> {
+        self.table_id
+    }
+
+    /// Build a table from `routes` with this table's dialect and table ID
+    pub(crate) fn with_routes(&self, routes: Vec<RouteEntry>) -> RoutingTable {
+        RoutingTable {
+            dialect: self.dialect,
+            table_id: self.table_id,
+            ..RoutingTable::from_routes(routes)
+        }
+    }
+
+    /// All entries in the table, in the order they were listed
+    #[must_use]
+    pub fn routes(&self) -> &[RouteEntry] {
+        &self.routes
     }
 
     /// Find the routing table entry that most-precisely matches the provided
     /// address.
     #[must_use]
     pub fn find_route_entry(&self, addr: IpAddr) -> Option<&RouteEntry> {
-        // TODO: implement a proper lookup table and/or short-circuit on an
-        // exact match
-        self.routes
-            .iter()
-            .filter(|route| route.contains(addr))
-            .fold(None, |old, new| match old {
-                None => Some(new),
-                Some(old) => Some(old.most_precise(new)),
-            })
+        self.find_route_entry_with(addr, &LookupOptions::default())
+    }
+
+    /// Find the routing table entry that most-precisely matches the provided
+    /// address, breaking ties according to `options`.  As in the OpenBSD and
+    /// Linux kernels, routes with a lower priority (or metric) win over
+    /// equally-precise ones with a higher priority.
+    #[must_use]
+    pub fn find_route_entry_with(
+        &self,
+        addr: IpAddr,
+        options: &LookupOptions,
+    ) -> Option<&RouteEntry> {
+        let mut candidates = self.best_candidates(addr, options.interface).into_iter();
+        match options.interface_order {
+            // Unlisted interfaces rank after all listed ones; `min_by_key`
+            // keeps the first of equal routes, preserving table order
+            Some(order) => candidates
+                .min_by_key(|route| order.rank(route.proto, &route.net_if).unwrap_or(usize::MAX)),
+            None => candidates.next(),
+        }
+    }
+
+    /// Find every path of the multipath route that most-precisely matches
+    /// the provided address, in table order.  The kernel spreads traffic
+    /// across the entries, which share the lowest priority and are flagged
+    /// [`RoutingFlag::Multipath`].  If the matching route isn't a multipath
+    /// route, only it is returned.
+    #[must_use]
+    pub fn find_multipath_entries(&self, addr: IpAddr) -> Vec<&RouteEntry> {
+        let mut candidates = self.best_candidates(addr, None);
+        if candidates
+            .first()
+            .is_some_and(|route| route.flags.contains(&RoutingFlag::Multipath))
+        {
+            candidates.retain(|route| route.flags.contains(&RoutingFlag::Multipath));
+        } else {
+            // E.g., Darwin's per-interface scoped default routes
+            candidates.truncate(1);
+        }
+        candidates
+    }
+
+    /// The most-precise entries matching the provided address with the lowest
+    /// priority, in table order, optionally only among the routes on
+    /// `interface`
+    fn best_candidates(&self, addr: IpAddr, interface: Option<&str>) -> Vec<&RouteEntry> {
+        // Routes without a priority rank after all prioritized ones
+        let rank = |route: &RouteEntry| route.priority.unwrap_or(u32::MAX);
+        let candidates = match interface {
+            Some(interface) => self
+                .index
+                .candidates_where(addr, |i| self.routes[i].net_if == interface)
+                .into_iter()
+                .map(|i| &self.routes[i])
+                .collect::<Vec<_>>(),
+            None => self
+                .index
+                .candidates(addr)
+                .iter()
+                .map(|&i| &self.routes[i])
+                .collect(),
+        };
+        let Some(best) = candidates.iter().map(|route| rank(route)).min() else {
+            return candidates;
+        };
+        candidates
+            .into_iter()
+            .filter(|route| rank(route) == best)
+            .collect()
     }
 
     #[must_use]
     pub fn default_gateways_for_netif(&self, net_if: &str) -> Option<&Vec<IpAddr>> {
         self.if_router.get(net_if)
     }
+
+    /// Group the routes directly on an interface, through a `link#N` gateway,
+    /// by the interface's index
+    #[must_use]
+    pub fn link_routes(&self) -> BTreeMap<u32, Vec<&RouteEntry>> {
+        let mut by_index: BTreeMap<u32, Vec<&RouteEntry>> = BTreeMap::new();
+        for route in &self.routes {
+            if let Entity::Link(link) = &route.gateway.entity {
+                if let Some(index) = link.index() {
+                    by_index.entry(index).or_default().push(route);
+                }
+            }
+        }
+        by_index
+    }
+}
+
+/// Accumulates the routes in the sections of `netstat -r` output
+#[derive(Debug, Default)]
+pub(crate) struct NetstatParser<'a> {
+    columns: Columns<'a>,
+    proto: Option<Protocol>,
+    routes: Vec<RouteEntry>,
+    dialect: NetstatDialect,
+    table_id: Option<u32>,
+}
+
+impl<'a> NetstatParser<'a> {
+    pub(crate) fn new(platform: NetstatPlatform) -> Self {
+        NetstatParser {
+            dialect: NetstatDialect::for_platform(platform),
+            ..NetstatParser::default()
+        }
+    }
+
+    /// Note the FIB named in the title line, e.g., `Routing tables (fib: 1)`
+    pub(crate) fn title(&mut self, line: &str) {
+        self.table_id = line
+            .split_once("(fib:")
+            .and_then(|(_, fib)| fib.trim_end().strip_suffix(')'))
+            .and_then(|fib| fib.trim().parse().ok());
+    }
+
+    /// The protocol of a section marker line, e.g., `Internet6:`
+    pub(crate) fn section_proto(line: &str) -> Option<Protocol> {
+        match line {
+            "Internet:" => Some(Protocol::V4),
+            "Internet6:" => Some(Protocol::V6),
+            _ => None,
+        }
+    }
+
+    /// Start a new section, with its column headers
+    pub(crate) fn section(&mut self, proto: Protocol, header: &'a str) {
+        self.proto = Some(proto);
+        self.columns = Columns::new(header);
+        self.dialect.observe_columns(proto, &self.columns);
+    }
+
+    /// Parse a route entry in the current section
+    pub(crate) fn entry(&mut self, line: &str) -> Result<(), Error> {
+        let proto = self.proto.ok_or(Error::EntryBeforeProto)?;
+        let route = RouteEntry::parse(proto, line, &self.columns, self.dialect.platform)?;
+        self.routes.push(route);
+        Ok(())
+    }
+
+    /// Whether any routes have been parsed
+    pub(crate) fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    pub(crate) fn finish(self) -> RoutingTable {
+        let table = RoutingTable::from_routes(self.routes).with_dialect(self.dialect);
+        match self.table_id {
+            Some(table_id) => table.with_table_id(table_id),
+            None => table,
+        }
+    }
 }
 
 /// Execute `netstat -rn` and return the output
@@ -124,12 +408,56 @@ impl RoutingTable {
 ///
 /// Returns an error if command execution fails, or the output is not UTF-8
 pub async fn execute_netstat() -> Result<String, Error> {
-    let output = Command::new(NETSTAT_PATH)
-        .arg("-rn")
+    execute_netstat_with(&NetstatOptions::default()).await
+}
+
+/// Execute `netstat -rn`, adjusted according to `options`, and return the
+/// output
+///
+/// # Errors
+///
+/// Returns an error if command execution fails, or the output is not UTF-8
+pub async fn execute_netstat_with(options: &NetstatOptions) -> Result<String, Error> {
+    let mut flags = String::from("-rn");
+    match options.platform {
+        NetstatPlatform::Darwin => {
+            if options.extended {
+                flags.push('l');
+            }
+            if options.wide {
+                flags.push('W');
+            }
+            if options.all {
+                flags.push('a');
+            }
+        }
+        // The wide listing carries the extended columns
+        NetstatPlatform::FreeBsd | NetstatPlatform::DragonFly => {
+            if options.extended || options.wide {
+                flags.push('W');
+            }
+        }
+        // OpenBSD and NetBSD have no wide listing, and always print the
+        // extended columns
+        NetstatPlatform::OpenBsd | NetstatPlatform::NetBsd => (),
+    }
+    let mut command = Command::new(options.platform.netstat_path());
+    command.arg(flags);
+    if let Some(fib) = options.fib {
+        let flag = match options.platform {
+            NetstatPlatform::OpenBsd => "-T",
+            _ => "-F",
+        };
+        command.arg(flag).arg(fib.to_string());
+    }
+    let output = command
         .stdin(std::process::Stdio::null())
         .output()
         .await
-        .map_err(Error::NetstatExec)?;
+        .map_err(|err| Error::NetstatExec {
+            path: options.platform.netstat_path(),
+            err,
+        })?;
     if !output.status.success() {
         return Err(Error::NetstatFail(output.status));
     }
@@ -139,8 +467,18 @@ pub async fn execute_netstat() -> Result<String, Error> {
 #[cfg(test)]
 mod tests {
     use super::Error;
-    use crate::{Destination, Entity, RoutingTable};
-    use std::{process::ExitStatus, string::FromUtf8Error};
+    use crate::{
+        Destination, Entity, LinkRef, NetstatDialect, NetstatLayout, NetstatPlatform, Protocol,
+        RouteEntry, RoutingFlag, RoutingTable,
+    };
+    use cidr::AnyIpCidr;
+    use std::{
+        collections::HashSet,
+        convert::TryFrom,
+        net::{IpAddr, Ipv4Addr, Ipv6Addr},
+        process::ExitStatus,
+        time::Duration,
+    };
 
     include!(concat!(env!("OUT_DIR"), "/sample_table.rs"));
 
@@ -148,10 +486,14 @@ mod tests {
     async fn coverage() {
         let rt = RoutingTable::from_netstat_output(SAMPLE_TABLE).expect("parse routing table");
         let _ = format!("{rt:?}");
-        let _ = format!(
-            "{:?}",
-            Error::NetstatExec(std::io::Error::from_raw_os_error(1))
-        );
+        let err = Error::NetstatExec {
+            path: NetstatPlatform::FreeBsd.netstat_path(),
+            err: std::io::Error::from_raw_os_error(1),
+        };
+        let _ = format!("{err:?}");
+        assert!(err
+            .to_string()
+            .starts_with("failed to execute /usr/bin/netstat: "));
         let _ = format!("{:?}", Error::NetstatFail(ExitStatus::default()));
         // This error is reachable only if the netstat command outputs invalid
         // UTF-8.
@@ -185,6 +527,19 @@ mod tests {
         let _ = format!("{rt:?}");
     }
 
+    #[test]
+    fn truncated_networks() {
+        let rt = RoutingTable::from_netstat_output(SAMPLE_TABLE).unwrap();
+        for (addr, dest) in [
+            ("127.1.2.3", "127.0.0.0/8"),
+            ("169.254.13.7", "169.254.0.0/16"),
+            ("192.168.64.99", "192.168.64.0/24"),
+        ] {
+            let entry = rt.find_route_entry(addr.parse().unwrap()).unwrap();
+            assert_eq!(entry.dest.to_string(), dest);
+        }
+    }
+
     #[test]
     fn missing_headers() {
         for section in ["", "6"] {
@@ -222,4 +577,556 @@ mod tests {
         // Coverage of debug formatting
         let _ = format!("{:?}", result.unwrap_err());
     }
+
+    #[test]
+    fn extended_table() {
+        let rt = RoutingTable::from_netstat_output(SAMPLE_TABLE_EXTENDED)
+            .expect("parse extended routing table");
+        let entry = rt.find_route_entry("1.1.1.1".parse().unwrap()).unwrap();
+        assert_eq!(entry.refs, Some(5));
+        assert_eq!(entry.use_count, Some(61273));
+        assert_eq!(entry.mtu, Some(1500));
+        let entry = rt.find_route_entry("224.0.0.251".parse().unwrap()).unwrap();
+        assert_eq!(entry.mtu, None);
+
+        // Plain `netstat -rn` output has none of the extended columns
+        let rt = RoutingTable::from_netstat_output(SAMPLE_TABLE).expect("parse routing table");
+        assert!(rt
+            .routes
+            .iter()
+            .all(|r| r.refs.is_none() && r.use_count.is_none() && r.mtu.is_none()));
+    }
+
+    #[test]
+    fn bad_counter() {
+        let input = SAMPLE_TABLE_EXTENDED.replace("61273", "lots");
+        let result = RoutingTable::from_netstat_output(&input);
+        assert!(matches!(
+            result,
+            Err(Error::RouteEntryParse(
+                crate::route_entry::Error::ParseCounter { column: "Use", .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn blank_cells() {
+        let rt = RoutingTable::from_netstat_output(SAMPLE_TABLE_BLANK_CELLS)
+            .expect("parse routing table with blank cells");
+        let entry = rt.find_route_entry("10.8.3.4".parse().unwrap()).unwrap();
+        assert_eq!(entry.gateway.to_string(), "utun3");
+        assert!(entry.flags.contains(&RoutingFlag::Cloning));
+        assert_eq!(entry.net_if, "utun3");
+        assert_eq!(entry.expires, None);
+
+        let entry = rt
+            .find_route_entry("192.168.100.200".parse().unwrap())
+            .unwrap();
+        assert_eq!(entry.net_if, "bridge100");
+        assert_eq!(entry.expires, Some(Duration::from_secs(1187)));
+
+        // A long destination and gateway push the later columns out of line
+        let entry = rt
+            .find_route_entry("2001:db8:1234:5678:9abc:def0:1234:5678".parse().unwrap())
+            .unwrap();
+        assert_eq!(
+            entry.gateway.to_string(),
+            "fe80::aaaa:bbbb:cccc:dddd%bridge100"
+        );
+        assert!(entry.flags.contains(&RoutingFlag::Host));
+        assert_eq!(entry.net_if, "bridge100");
+
+        let entry = rt.routes().last().unwrap();
+        assert_eq!(entry.gateway.to_string(), "utun0");
+        assert!(entry.flags.contains(&RoutingFlag::PrCloning));
+        assert_eq!(entry.net_if, "utun0");
+    }
+
+    #[test]
+    fn link_routes() {
+        let rt = RoutingTable::from_netstat_output(SAMPLE_TABLE).expect("parse routing table");
+        let by_index = rt.link_routes();
+        assert_eq!(
+            by_index.keys().copied().collect::<Vec<_>>(),
+            [1, 5, 6, 7, 8]
+        );
+        assert_eq!(by_index[&5].len(), 10);
+        assert!(by_index[&5]
+            .iter()
+            .all(|route| route.gateway.to_string() == "link#5"));
+
+        // Blank gateways refer to the interface by name
+        let rt = RoutingTable::from_netstat_output(SAMPLE_TABLE_BLANK_CELLS)
+            .expect("parse routing table with blank cells");
+        let entry = rt.find_route_entry("10.8.3.4".parse().unwrap()).unwrap();
+        assert_eq!(
+            entry.gateway.entity,
+            Entity::Link(LinkRef::Name("utun3".into()))
+        );
+        assert!(rt
+            .link_routes()
+            .values()
+            .flatten()
+            .all(|route| route.net_if != "utun3"));
+
+        // So do gateways that match the row's interface
+        let input = SAMPLE_TABLE
+            .replace(
+                "169.254            link#5             UCS               en0",
+                "169.254            en0                UCS               en0",
+            )
+            .replace(
+                "224.0.0/4          link#5             UmCS              en0",
+                "224.0.0/4          vpn-a              UmCS            vpn-a",
+            );
+        let rt = RoutingTable::from_netstat_output(&input).expect("parse interface gateways");
+        for (addr, name) in [("169.254.1.1", "en0"), ("224.0.0.1", "vpn-a")] {
+            let entry = rt.find_route_entry(addr.parse().unwrap()).unwrap();
+            assert_eq!(
+                entry.gateway.entity,
+                Entity::Link(LinkRef::Name(name.into()))
+            );
+            assert_eq!(entry.net_if, name);
+        }
+    }
+
+    #[test]
+    fn embedded_scope() {
+        for (dest, decoded) in [
+            ("fe80:4::1", "fe80::1%4"),
+            ("fe80:4::1%en0", "fe80::1%en0"),
+            ("fe80:4::/64", "fe80::/64%4"),
+            ("ff02:4::fb", "ff02::fb%4"),
+            ("fe80::1%lo0", "fe80::1%lo0"),
+            ("fe80::%lo0/64", "fe80::/64%lo0"),
+            ("2001:4::1", "2001:4::1"),
+            ("ff0e:4::1", "ff0e:4::1"),
+        ] {
+            let parsed = crate::route_entry::parse_destination(dest).unwrap();
+            assert_eq!(parsed.to_string(), decoded, "{dest}");
+        }
+    }
+
+    #[test]
+    fn zoned_network_prefix_length() {
+        // Reassembling `fe80::%lo0/64` without its zone used to drop the `/`,
+        // parsing it as the host fe80::64
+        let dest = crate::route_entry::parse_destination("fe80::%lo0/64").unwrap();
+        assert_eq!(dest.to_string(), "fe80::/64%lo0");
+        let rt = RoutingTable::from_netstat_output(SAMPLE_TABLE).unwrap();
+        assert!(rt
+            .routes()
+            .iter()
+            .all(|route| route.dest.to_string() != "fe80::64%lo0"));
+    }
+
+    #[test]
+    fn netmask_column() {
+        let rt = RoutingTable::from_netstat_output(SAMPLE_TABLE_NETMASK)
+            .expect("parse routing table with netmasks");
+        for (addr, dest) in [
+            ("10.8.3.4", "10.8.0.0/16"),
+            ("127.1.2.3", "127.0.0.0/8"),
+            ("127.0.0.1", "127.0.0.1/32"),
+            ("192.168.64.9", "192.168.64.0/24"),
+            ("192.168.64.1", "192.168.64.1/32"),
+            ("2001:db8::5", "2001:db8::/64"),
+            ("::1", "::1/128"),
+        ] {
+            let entry = rt.find_route_entry(addr.parse().unwrap()).unwrap();
+            assert_eq!(
+                entry.dest.entity,
+                Entity::Cidr(dest.parse().unwrap()),
+                "{addr}"
+            );
+        }
+        let entry = rt.find_route_entry("1.1.1.1".parse().unwrap()).unwrap();
+        assert_eq!(entry.dest.entity, Entity::Default);
+        assert_eq!(entry.net_if, "en0");
+    }
+
+    #[test]
+    fn netmask_notation() {
+        for (dest, cidr) in [
+            ("10.0.0.0/255.0.0.0", "10.0.0.0/8"),
+            ("10.8/255.255.0.0", "10.8.0.0/16"),
+            ("192.168.64.0/0xffffff00", "192.168.64.0/24"),
+            ("2001:db8::/ffff:ffff::", "2001:db8::/32"),
+        ] {
+            let parsed = crate::route_entry::parse_destination(dest).unwrap();
+            assert_eq!(parsed.entity, Entity::Cidr(cidr.parse().unwrap()), "{dest}");
+        }
+        for dest in ["10.0.0.0/255.0.255.0", "10.0.0.0/0xff00ff00"] {
+            assert!(matches!(
+                crate::route_entry::parse_destination(dest),
+                Err(crate::route_entry::Error::NonContiguousNetmask { .. })
+            ));
+        }
+        assert!(matches!(
+            crate::route_entry::parse_destination("10.0.0.0/0xfffffg00"),
+            Err(crate::route_entry::Error::ParseNetmask { .. })
+        ));
+
+        let input = SAMPLE_TABLE_NETMASK.replace("255.255.0.0", "255.0.255.0");
+        assert!(matches!(
+            RoutingTable::from_netstat_output(&input),
+            Err(Error::RouteEntryParse(
+                crate::route_entry::Error::NonContiguousNetmask { .. }
+            ))
+        ));
+    }
+
+    /// The reference lookup: a linear scan folded with `most_precise`
+    fn find_route_entry_linear(rt: &RoutingTable, addr: IpAddr) -> Option<&RouteEntry> {
+        rt.routes
+            .iter()
+            .filter(|route| route.contains(addr))
+            .fold(None, |old, new| match old {
+                None => Some(new),
+                Some(old) => {
+                    // Of equally-precise routes, the lower priority wins
+                    let rank = |route: &RouteEntry| route.priority.unwrap_or(u32::MAX);
+                    let tied = std::ptr::eq(old.most_precise(new), old)
+                        && std::ptr::eq(new.most_precise(old), new);
+                    Some(if tied && rank(new) < rank(old) {
+                        new
+                    } else {
+                        old.most_precise(new)
+                    })
+                }
+            })
+    }
+
+    fn assert_same_lookup(rt: &RoutingTable, addr: IpAddr) {
+        let indexed = rt.find_route_entry(addr).map(std::ptr::from_ref);
+        let linear = find_route_entry_linear(rt, addr).map(std::ptr::from_ref);
+        assert_eq!(indexed, linear, "lookup of {addr} differs");
+    }
+
+    fn route(proto: Protocol, dest: Entity, gateway: Entity, net_if: &str) -> RouteEntry {
+        RouteEntry {
+            proto,
+            dest: Destination {
+                entity: dest,
+                zone: None,
+            },
+            gateway: Destination {
+                entity: gateway,
+                zone: None,
+            },
+            flags: HashSet::new(),
+            net_if: net_if.into(),
+            expires: None,
+            refs: None,
+            use_count: None,
+            mtu: None,
+            priority: None,
+            label: None,
+            origin: None,
+            scope: None,
+            route_type: None,
+        }
+    }
+
+    #[test]
+    fn index_matches_linear_scan() {
+        let rt = RoutingTable::from_netstat_output(SAMPLE_TABLE).expect("parse routing table");
+        for addr in [
+            "1.1.1.1",
+            "127.0.0.1",
+            "0.0.0.127",
+            "192.168.64.1",
+            "192.168.64.23",
+            "224.0.0.251",
+            "255.255.255.255",
+            "::1",
+            "fe80::1",
+            "fe80::21c1:53b6:e09d:8ea1",
+            "2001:db8::1",
+        ] {
+            assert_same_lookup(&rt, addr.parse().unwrap());
+        }
+    }
+
+    #[test]
+    fn index_matches_linear_scan_synthetic() {
+        let gw = |s: &str| Entity::Cidr(AnyIpCidr::new_host(s.parse().unwrap()));
+        let mut routes = vec![
+            route(Protocol::V4, Entity::Default, gw("10.0.0.1"), "en0"),
+            route(Protocol::V4, Entity::Default, gw("10.0.0.2"), "en1"),
+            route(Protocol::V6, Entity::Default, gw("fe80::1"), "en0"),
+        ];
+        // Overlapping prefixes of every length, with duplicates to exercise
+        // the first-entry-wins tie break.
+        for i in 0..4096_u32 {
+            let len = u8::try_from(i % 33).unwrap();
+            let addr = i.wrapping_mul(0x9e37_79b9);
+            let masked = addr & u32::MAX.checked_shl(32 - u32::from(len)).unwrap_or(0);
+            let cidr = AnyIpCidr::new(IpAddr::V4(Ipv4Addr::from(masked)), len).unwrap();
+            let net_if = format!("en{}", i % 3);
+            routes.push(route(
+                Protocol::V4,
+                Entity::Cidr(cidr),
+                gw("10.0.0.1"),
+                &net_if,
+            ));
+            routes.push(route(
+                Protocol::V4,
+                Entity::Cidr(cidr),
+                gw("10.0.0.2"),
+                &net_if,
+            ));
+
+            let len = u8::try_from(i % 129).unwrap();
+            let addr = u128::from(i).wrapping_mul(0x9e37_79b9_7f4a_7c15_f39c_c060_5ced_c834);
+            let masked = addr & u128::MAX.checked_shl(128 - u32::from(len)).unwrap_or(0);
+            let cidr = AnyIpCidr::new(IpAddr::V6(Ipv6Addr::from(masked)), len).unwrap();
+            routes.push(route(
+                Protocol::V6,
+                Entity::Cidr(cidr),
+                gw("fe80::1"),
+                &net_if,
+            ));
+        }
+        let rt = RoutingTable::from_routes(routes);
+
+        for i in 0..4096_u32 {
+            let v4 = i.wrapping_mul(0x9e37_79b9) ^ (i << 3);
+            assert_same_lookup(&rt, IpAddr::V4(v4.into()));
+            let v6 = u128::from(i).wrapping_mul(0x9e37_79b9_7f4a_7c15_f39c_c060_5ced_c834)
+                ^ u128::from(i);
+            assert_same_lookup(&rt, IpAddr::V6(v6.into()));
+        }
+    }
+
+    /// Parse a corpus sample, check its dialect against the directory it's
+    /// filed under, and check that lookups of every destination match the
+    /// linear scan
+    fn check_corpus_sample(sample: &str, table: &str) {
+        let mut expected = NetstatDialect::default();
+        let dir = sample.split('/').next().unwrap_or_default();
+        for word in dir.split('-') {
+            match word {
+                "legacy" => expected.layout = NetstatLayout::Legacy,
+                "modern" => expected.layout = NetstatLayout::Modern,
+                "extended" => expected.layout = NetstatLayout::Extended,
+                "wide" => expected.wide = true,
+                "freebsd" => expected.platform = NetstatPlatform::FreeBsd,
+                "openbsd" => expected.platform = NetstatPlatform::OpenBsd,
+                "netbsd" => expected.platform = NetstatPlatform::NetBsd,
+                "dragonfly" => expected.platform = NetstatPlatform::DragonFly,
+                _ => panic!("no dialect for {}", sample),
+            }
+        }
+        let rt = RoutingTable::from_netstat_output_with(table, expected.platform)
+            .unwrap_or_else(|err| panic!("parse {}: {}", sample, err));
+        assert_eq!(rt.dialect(), expected, "dialect of {sample}");
+        // The platform can be told from the output alone, too
+        let detected = RoutingTable::from_netstat_output(table)
+            .unwrap_or_else(|err| panic!("parse {}: {}", sample, err));
+        assert_eq!(detected.dialect(), expected, "detected dialect of {sample}");
+        for route in rt.routes() {
+            if let Entity::Cidr(cidr) = route.dest.entity {
+                if let Some(addr) = cidr.first_address() {
+                    assert_same_lookup(&rt, addr);
+                }
+            }
+        }
+    }
+
+    macro_rules! netstat_corpus {
+        ($($test:ident => ($sample:expr, $table:ident),)*) => {
+            $(
+                #[test]
+                fn $test() {
+                    check_corpus_sample($sample, $table);
+                }
+            )*
+        };
+    }
+
+    include!(concat!(env!("OUT_DIR"), "/netstat_corpus.rs"));
+
+    #[test]
+    fn freebsd() {
+        let rt =
+            RoutingTable::from_netstat_output_with(NETSTAT_FREEBSD_13_2, NetstatPlatform::FreeBsd)
+                .expect("parse FreeBSD routing table");
+        let entry = rt.find_route_entry("203.0.113.9".parse().unwrap()).unwrap();
+        assert!(entry.flags.contains(&RoutingFlag::Blackhole));
+        let entry = rt
+            .find_route_entry("198.51.100.9".parse().unwrap())
+            .unwrap();
+        assert!(entry.flags.contains(&RoutingFlag::Reject));
+        let entry = rt.find_route_entry("1.1.1.1".parse().unwrap()).unwrap();
+        assert_eq!(entry.net_if, "vtnet0");
+        let entry = rt
+            .find_route_entry("fe80::5a9c:fcff:fe01:2a3b".parse().unwrap())
+            .unwrap();
+        assert_eq!(entry.dest.zone.as_deref(), Some("vtnet0"));
+        assert_eq!(entry.net_if, "lo0");
+
+        // Darwin-only flag letters mean nothing on FreeBSD
+        assert_eq!(
+            RoutingFlag::from_letter('I', NetstatPlatform::Darwin),
+            RoutingFlag::IfScope
+        );
+        assert_eq!(
+            RoutingFlag::from_letter('I', NetstatPlatform::FreeBsd),
+            RoutingFlag::Unknown
+        );
+
+        // Wide output is recognizable without being told the platform
+        let rt = RoutingTable::from_netstat_output(NETSTAT_FREEBSD_WIDE_12_4)
+            .expect("parse FreeBSD wide routing table");
+        assert_eq!(rt.dialect().platform, NetstatPlatform::FreeBsd);
+        assert!(rt.dialect().wide);
+        let entry = rt.find_route_entry("1.1.1.1".parse().unwrap()).unwrap();
+        assert_eq!(entry.use_count, Some(184_720));
+        assert_eq!(entry.mtu, Some(1500));
+        assert_eq!(entry.refs, None);
+        let rt = RoutingTable::from_netstat_output(NETSTAT_FREEBSD_WIDE_14_0)
+            .expect("parse FreeBSD 14 wide routing table");
+        assert_eq!(rt.dialect().platform, NetstatPlatform::FreeBsd);
+    }
+
+    #[test]
+    fn openbsd() {
+        // OpenBSD output is recognizable by its `Prio` column
+        let rt = RoutingTable::from_netstat_output(NETSTAT_OPENBSD_EXTENDED_7_4)
+            .expect("parse OpenBSD routing table");
+        assert_eq!(rt.dialect().platform, NetstatPlatform::OpenBsd);
+
+        // Both default routes share a priority, making a multipath route
+        let addr = "1.1.1.1".parse().unwrap();
+        let entries = rt.find_multipath_entries(addr);
+        assert_eq!(
+            entries
+                .iter()
+                .map(|r| r.net_if.as_str())
+                .collect::<Vec<_>>(),
+            ["em0", "em1"]
+        );
+        assert!(entries
+            .iter()
+            .all(|r| r.flags.contains(&RoutingFlag::Multipath) && r.priority == Some(8)));
+        assert_eq!(rt.find_route_entry(addr).unwrap().net_if, "em0");
+
+        // Darwin's scoped default routes, one per interface, aren't
+        let darwin = RoutingTable::from_netstat_output(SAMPLE_TABLE).unwrap();
+        let entries = darwin.find_multipath_entries("2606:4700::1111".parse().unwrap());
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].net_if, "utun0");
+
+        // The lower priority wins, even though it's listed second
+        let entry = rt.find_route_entry("10.20.1.1".parse().unwrap()).unwrap();
+        assert_eq!(entry.priority, Some(8));
+        assert_eq!(entry.net_if, "em0");
+        assert_eq!(entry.label, None);
+
+        let entry = rt.find_route_entry("10.0.0.2".parse().unwrap()).unwrap();
+        assert!(entry.flags.contains(&RoutingFlag::Local));
+        assert_eq!(entry.mtu, None);
+        assert_eq!(entry.refs, Some(0));
+        assert_eq!(entry.use_count, Some(211));
+        let entry = rt.find_route_entry("10.0.0.1".parse().unwrap()).unwrap();
+        assert!(entry.flags.contains(&RoutingFlag::WasCloned));
+        assert!(entry.flags.contains(&RoutingFlag::Cached));
+        let entry = rt.find_route_entry("10.0.0.9".parse().unwrap()).unwrap();
+        assert!(entry.flags.contains(&RoutingFlag::Connected));
+        let entry = rt
+            .find_route_entry("198.51.100.1".parse().unwrap())
+            .unwrap();
+        assert!(entry.flags.contains(&RoutingFlag::Mpls));
+        let entry = rt.find_route_entry("127.0.0.2".parse().unwrap()).unwrap();
+        assert_eq!(entry.mtu, Some(32768));
+        let entry = rt.find_route_entry("239.1.1.1".parse().unwrap()).unwrap();
+        assert_eq!(entry.net_if, "lo0");
+
+        // Labels appear when listed
+        let rt = RoutingTable::from_netstat_output(NETSTAT_OPENBSD_EXTENDED_7_4_LABELS)
+            .expect("parse OpenBSD routing table with labels");
+        let entry = rt.find_route_entry("10.20.1.1".parse().unwrap()).unwrap();
+        assert_eq!(entry.label.as_deref(), Some("primary"));
+        assert_eq!(
+            rt.find_multipath_entries("10.20.1.1".parse().unwrap())
+                .len(),
+            1
+        );
+
+        // Darwin's interface-scope flag means nothing on OpenBSD, and `c`
+        // marks a cloned route
+        assert_eq!(
+            RoutingFlag::from_letter('I', NetstatPlatform::OpenBsd),
+            RoutingFlag::Unknown
+        );
+        assert_eq!(
+            RoutingFlag::from_letter('c', NetstatPlatform::Darwin),
+            RoutingFlag::PrCloning
+        );
+        assert_eq!(RoutingFlag::from_name("MPATH"), RoutingFlag::Multipath);
+    }
+
+    #[test]
+    fn netbsd_and_dragonfly() {
+        // NetBSD is recognizable by its `Interface` column
+        let rt = RoutingTable::from_netstat_output(NETSTAT_NETBSD_EXTENDED_10_0)
+            .expect("parse NetBSD routing table");
+        assert_eq!(rt.dialect().platform, NetstatPlatform::NetBsd);
+        let entry = rt.find_route_entry("1.1.1.1".parse().unwrap()).unwrap();
+        assert_eq!(entry.net_if, "wm0");
+        assert_eq!((entry.refs, entry.use_count, entry.mtu), (None, None, None));
+        let entry = rt.find_route_entry("10.0.2.15".parse().unwrap()).unwrap();
+        assert!(entry.flags.contains(&RoutingFlag::Local));
+        assert_eq!(entry.net_if, "lo0");
+        let entry = rt.find_route_entry("10.0.2.2".parse().unwrap()).unwrap();
+        assert!(entry.flags.contains(&RoutingFlag::WasCloned));
+        let entry = rt.find_route_entry("10.9.0.1".parse().unwrap()).unwrap();
+        assert!(entry.flags.contains(&RoutingFlag::Proto3));
+        let entry = rt.find_route_entry("172.16.5.1".parse().unwrap()).unwrap();
+        assert!(entry.flags.contains(&RoutingFlag::Kernel));
+        let entry = rt
+            .find_route_entry("fe80::5054:ff:fe12:3456".parse().unwrap())
+            .unwrap();
+        assert_eq!(entry.dest.zone.as_deref(), Some("wm0"));
+
+        // DragonFly by its narrow flags field, before any routes are read
+        let rt = RoutingTable::from_netstat_output(NETSTAT_DRAGONFLY_LEGACY_6_4)
+            .expect("parse DragonFly routing table");
+        assert_eq!(rt.dialect().platform, NetstatPlatform::DragonFly);
+        let entry = rt.find_route_entry("198.18.0.1".parse().unwrap()).unwrap();
+        assert!(entry.flags.contains(&RoutingFlag::Kernel));
+        let entry = rt.find_route_entry("1.1.1.1".parse().unwrap()).unwrap();
+        assert!(entry.flags.contains(&RoutingFlag::PrCloning));
+        assert_eq!(entry.refs, Some(3));
+        let entry = rt.find_route_entry("192.168.7.1".parse().unwrap()).unwrap();
+        assert!(entry.flags.contains(&RoutingFlag::WasCloned));
+        assert_eq!(entry.expires, Some(std::time::Duration::from_secs(1180)));
+
+        // Both can also be named explicitly
+        let rt = RoutingTable::from_netstat_output_with(
+            NETSTAT_DRAGONFLY_LEGACY_6_4,
+            NetstatPlatform::DragonFly,
+        )
+        .unwrap();
+        assert_eq!(rt.dialect().platform, NetstatPlatform::DragonFly);
+        assert_eq!(
+            RoutingFlag::from_letter('l', NetstatPlatform::NetBsd),
+            RoutingFlag::Local
+        );
+        assert_eq!(
+            RoutingFlag::from_letter('l', NetstatPlatform::Darwin),
+            RoutingFlag::Unknown
+        );
+    }
+
+    #[test]
+    fn dialect() {
+        let rt = RoutingTable::from_netstat_output(SAMPLE_TABLE).expect("parse routing table");
+        assert_eq!(rt.dialect(), NetstatDialect::default());
+        let rt = RoutingTable::from_netstat_output(SAMPLE_TABLE_EXTENDED)
+            .expect("parse extended routing table");
+        assert_eq!(rt.dialect().layout, NetstatLayout::Extended);
+        assert!(!rt.dialect().wide && !rt.dialect().all);
+    }
 }
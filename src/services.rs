@@ -0,0 +1,319 @@
+use crate::{route_entry::parse_mac, RouteEntry, RoutingTable};
+use mac_address::MacAddress;
+use std::{process::ExitStatus, string::FromUtf8Error};
+use tokio::process::Command;
+
+const NETWORKSETUP_PATH: &str = "/usr/sbin/networksetup";
+
+/// The network services and hardware ports known to the System
+/// Configuration framework, as listed by `networksetup`
+#[derive(Debug, Clone, Default)]
+pub struct NetworkServices {
+    services: Vec<NetworkService>,
+    ports: Vec<HardwarePort>,
+}
+
+/// A network service, e.g., `Wi-Fi`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkService {
+    /// User-visible service name
+    pub name: String,
+
+    /// Position in the service order, starting at 1.  `None` for disabled
+    /// services.
+    pub order: Option<u32>,
+
+    /// Name of the hardware port the service runs on
+    pub hardware_port: Option<String>,
+
+    /// BSD device name, e.g., `en0`.  `None` for services without a fixed
+    /// device, such as most VPNs.
+    pub device: Option<String>,
+}
+
+/// A hardware port, as listed by `networksetup -listallhardwareports`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HardwarePort {
+    pub name: String,
+    pub device: String,
+    pub ether: Option<MacAddress>,
+}
+
+/// Various errors
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to execute {NETWORKSETUP_PATH}: {0}")]
+    NetworksetupExec(std::io::Error),
+    #[error("failed to list network services: {0}")]
+    NetworksetupFail(ExitStatus),
+    #[error("networksetup output not UTF-8")]
+    NetworksetupUtf8(FromUtf8Error),
+    #[error("service details found before service name: {0:?}")]
+    DetailBeforeService(String),
+    #[error("hardware port details found before port name: {0:?}")]
+    DetailBeforePort(String),
+    #[error("invalid {field} {value:?} in networksetup output")]
+    BadValue { field: &'static str, value: String },
+}
+
+impl NetworkServices {
+    /// Query the service order and hardware ports using the `networksetup`
+    /// command.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `networksetup` command fails to execute, or
+    /// returns unparseable output.
+    pub async fn load_from_networksetup() -> Result<Self, Error> {
+        let service_order = execute_networksetup("-listnetworkserviceorder").await?;
+        let hardware_ports = execute_networksetup("-listallhardwareports").await?;
+        Self::parse(&service_order, &hardware_ports)
+    }
+
+    /// Parse the output of `networksetup -listnetworkserviceorder` and
+    /// `networksetup -listallhardwareports`.  Services listed without a
+    /// device take it from the hardware port of the same name, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either output contains unparseable values
+    pub fn parse(service_order: &str, hardware_ports: &str) -> Result<Self, Error> {
+        let mut services = parse_service_order(service_order)?;
+        let ports = parse_hardware_ports(hardware_ports)?;
+        for service in &mut services {
+            if service.device.is_none() {
+                service.device = service
+                    .hardware_port
+                    .as_deref()
+                    .and_then(|name| ports.iter().find(|port| port.name == name))
+                    .map(|port| port.device.clone());
+            }
+        }
+        Ok(NetworkServices { services, ports })
+    }
+
+    /// All services, in service order, with disabled services in their listed
+    /// position
+    pub fn iter(&self) -> impl Iterator<Item = &NetworkService> {
+        self.services.iter()
+    }
+
+    /// All hardware ports, in listing order
+    pub fn ports(&self) -> impl Iterator<Item = &HardwarePort> {
+        self.ports.iter()
+    }
+
+    /// Find a service by name
+    #[must_use]
+    pub fn by_name(&self, name: &str) -> Option<&NetworkService> {
+        self.services.iter().find(|service| service.name == name)
+    }
+
+    /// Find the service that owns a device.  When several services share a
+    /// device, the first enabled one wins.
+    #[must_use]
+    pub fn by_device(&self, device: &str) -> Option<&NetworkService> {
+        let mut matches = self
+            .services
+            .iter()
+            .filter(|service| service.device.as_deref() == Some(device));
+        matches
+            .clone()
+            .find(|service| service.order.is_some())
+            .or_else(|| matches.next())
+    }
+}
+
+impl RoutingTable {
+    /// Pair each route with the network service that owns its `Netif`.
+    /// Interfaces without a service, e.g., `lo0`, pair with `None`.
+    pub fn with_services<'a>(
+        &'a self,
+        services: &'a NetworkServices,
+    ) -> impl Iterator<Item = (&'a RouteEntry, Option<&'a NetworkService>)> {
+        self.routes()
+            .iter()
+            .map(move |route| (route, services.by_device(&route.net_if)))
+    }
+}
+
+async fn execute_networksetup(arg: &str) -> Result<String, Error> {
+    let output = Command::new(NETWORKSETUP_PATH)
+        .arg(arg)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await
+        .map_err(Error::NetworksetupExec)?;
+    if !output.status.success() {
+        return Err(Error::NetworksetupFail(output.status));
+    }
+    String::from_utf8(output.stdout).map_err(Error::NetworksetupUtf8)
+}
+
+/// Parse the output of `networksetup -listnetworkserviceorder`, e.g.,
+///
+/// ```text
+/// (2) Wi-Fi
+/// (Hardware Port: Wi-Fi, Device: en0)
+/// ```
+fn parse_service_order(output: &str) -> Result<Vec<NetworkService>, Error> {
+    let mut services: Vec<NetworkService> = vec![];
+    for line in output.lines() {
+        let line = line.trim();
+        if let Some(details) = line.strip_prefix("(Hardware Port:") {
+            let service = services
+                .last_mut()
+                .ok_or_else(|| Error::DetailBeforeService(line.into()))?;
+            let details = details.trim_end_matches(')');
+            // Port names may contain commas, so split at the device instead
+            let (port, device) = details.rsplit_once(", Device:").unwrap_or((details, ""));
+            let (port, device) = (port.trim(), device.trim());
+            service.hardware_port = (!port.is_empty()).then(|| port.into());
+            service.device = (!device.is_empty()).then(|| device.into());
+            continue;
+        }
+        let Some((order, name)) = line
+            .strip_prefix('(')
+            .and_then(|line| line.split_once(") "))
+        else {
+            // The disabled-service legend, and blank lines
+            continue;
+        };
+        let order = match order {
+            "*" => None,
+            order => Some(order.parse().map_err(|_| Error::BadValue {
+                field: "service order",
+                value: order.into(),
+            })?),
+        };
+        services.push(NetworkService {
+            name: name.into(),
+            order,
+            hardware_port: None,
+            device: None,
+        });
+    }
+    Ok(services)
+}
+
+/// Parse the output of `networksetup -listallhardwareports`
+fn parse_hardware_ports(output: &str) -> Result<Vec<HardwarePort>, Error> {
+    let mut ports: Vec<HardwarePort> = vec![];
+    for line in output.lines() {
+        let Some((key, value)) = line.split_once(": ") else {
+            // Blank lines, and the VLAN section
+            continue;
+        };
+        let value = value.trim();
+        if key == "Hardware Port" {
+            ports.push(HardwarePort {
+                name: value.into(),
+                device: String::new(),
+                ether: None,
+            });
+            continue;
+        }
+        let port = ports
+            .last_mut()
+            .ok_or_else(|| Error::DetailBeforePort(line.into()))?;
+        match key {
+            "Device" => port.device = value.into(),
+            "Ethernet Address" if value != "N/A" => {
+                port.ether = Some(parse_mac(value).map_err(|_| Error::BadValue {
+                    field: "Ethernet address",
+                    value: value.into(),
+                })?);
+            }
+            _ => (),
+        }
+    }
+    Ok(ports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, NetworkServices};
+    use crate::RoutingTable;
+
+    include!(concat!(env!("OUT_DIR"), "/sample_table.rs"));
+
+    #[test]
+    fn parse_services() {
+        let services =
+            NetworkServices::parse(NETWORKSETUP_SERVICE_ORDER, NETWORKSETUP_HARDWARE_PORTS)
+                .expect("parse networksetup");
+        let names: Vec<_> = services.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "USB 10/100 LAN",
+                "Wi-Fi",
+                "Thunderbolt Bridge",
+                "Bluetooth PAN",
+                "Corp VPN"
+            ]
+        );
+
+        let wifi = services.by_device("en0").unwrap();
+        assert_eq!(wifi.name, "Wi-Fi");
+        assert_eq!(wifi.order, Some(2));
+        assert_eq!(wifi.hardware_port.as_deref(), Some("Wi-Fi"));
+
+        assert_eq!(services.by_name("Bluetooth PAN").unwrap().order, None);
+        let vpn = services.by_name("Corp VPN").unwrap();
+        assert_eq!(vpn.order, Some(4));
+        assert_eq!(vpn.hardware_port.as_deref(), Some("com.example.vpn"));
+        assert!(vpn.device.is_none());
+
+        assert_eq!(services.ports().count(), 5);
+        let thunderbolt = services.ports().find(|p| p.device == "en1").unwrap();
+        assert_eq!(thunderbolt.name, "Thunderbolt 1");
+        assert_eq!(thunderbolt.ether.unwrap().to_string(), "36:D1:08:4A:C0:01");
+        assert!(services
+            .ports()
+            .all(|p| p.device != "en6" || p.ether.is_none()));
+    }
+
+    #[test]
+    fn device_from_hardware_port() {
+        let services = NetworkServices::parse(
+            "(1) Wi-Fi\n(Hardware Port: Wi-Fi, Device: )\n",
+            NETWORKSETUP_HARDWARE_PORTS,
+        )
+        .unwrap();
+        assert_eq!(
+            services.by_name("Wi-Fi").unwrap().device.as_deref(),
+            Some("en0")
+        );
+    }
+
+    #[test]
+    fn route_services() {
+        let services =
+            NetworkServices::parse(NETWORKSETUP_SERVICE_ORDER, NETWORKSETUP_HARDWARE_PORTS)
+                .unwrap();
+        let rt = RoutingTable::from_netstat_output(SAMPLE_TABLE).unwrap();
+        for (route, service) in rt.with_services(&services) {
+            match route.net_if.as_str() {
+                "en0" => assert_eq!(service.unwrap().name, "Wi-Fi"),
+                _ => assert!(service.is_none()),
+            }
+        }
+    }
+
+    #[test]
+    fn bad_services() {
+        assert!(matches!(
+            NetworkServices::parse("(Hardware Port: Wi-Fi, Device: en0)", ""),
+            Err(Error::DetailBeforeService(_))
+        ));
+        assert!(matches!(
+            NetworkServices::parse("(x) Wi-Fi", ""),
+            Err(Error::BadValue { .. })
+        ));
+        assert!(matches!(
+            NetworkServices::parse("", "Device: en0\n"),
+            Err(Error::DetailBeforePort(_))
+        ));
+    }
+}
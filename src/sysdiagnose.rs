@@ -0,0 +1,338 @@
+use crate::{DnsConfiguration, InterfaceTable, RoutingTable};
+use flate2::read::GzDecoder;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+// Capture paths are relative to the top of the archive.  sysdiagnose collects
+// them with configd's `get-network-info` script, into `network-info/` (macOS
+// 14 Sonoma).  Captures that aren't found are reported (the routing table by
+// `Error::MissingCapture`, the others in `SysdiagnoseSnapshot::missing`), so a
+// path moved by a later release doesn't go unnoticed.
+
+/// `netstat` output, including `netstat -n -r -a -l` among other listings
+const NETSTAT_CAPTURE: &str = "network-info/netstat.txt";
+
+/// `ifconfig -a -L -b -m -r -v -v` output
+const IFCONFIG_CAPTURE: &str = "network-info/ifconfig.txt";
+
+/// `scutil --dns` output
+const SCUTIL_DNS_CAPTURE: &str = "network-info/dns-configuration.txt";
+
+/// The routing data captured in a sysdiagnose
+#[derive(Debug)]
+pub struct SysdiagnoseSnapshot {
+    /// The routing table, from the first `netstat -r` listing found
+    pub routing_table: RoutingTable,
+
+    /// Network interfaces, if `ifconfig` output was captured
+    pub interfaces: Option<InterfaceTable>,
+
+    /// Resolver configuration, if `scutil --dns` output was captured
+    pub dns: Option<DnsConfiguration>,
+
+    /// The optional captures that weren't found
+    pub missing: Vec<MissingCapture>,
+}
+
+/// An optional capture that wasn't found in a sysdiagnose
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MissingCapture {
+    /// What the capture holds, e.g., `ifconfig`
+    pub capture: &'static str,
+
+    /// The path it was looked for under
+    pub path: &'static str,
+}
+
+impl std::fmt::Display for MissingCapture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no {} capture (looked for {})", self.capture, self.path)
+    }
+}
+
+/// Various errors
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("reading {}: {err}", .path.display())]
+    Read { path: PathBuf, err: std::io::Error },
+    #[error("reading sysdiagnose archive: {0}")]
+    Archive(std::io::Error),
+    #[error("no {capture} capture in sysdiagnose (looked for {path})")]
+    MissingCapture {
+        capture: &'static str,
+        path: &'static str,
+    },
+    #[error("no routing table listing in {file}")]
+    NoRoutingTable { file: &'static str },
+    #[error("{file} is not UTF-8")]
+    NotUtf8 { file: &'static str },
+    #[error("parsing {file}: {err}")]
+    Interfaces {
+        file: &'static str,
+        err: crate::interface::Error,
+    },
+    #[error("parsing {file}: {err}")]
+    Dns {
+        file: &'static str,
+        err: crate::dns::Error,
+    },
+}
+
+impl SysdiagnoseSnapshot {
+    /// Load the captures from a sysdiagnose `.tar.gz` archive, or the
+    /// directory it extracts to.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the archive can't be read, the routing table
+    /// capture is missing, or a capture is unparseable
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            Self::from_directory(path)
+        } else {
+            let file = File::open(path).map_err(|err| Error::Read {
+                path: path.to_owned(),
+                err,
+            })?;
+            Self::from_archive(file)
+        }
+    }
+
+    /// Load the captures from an extracted sysdiagnose directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a capture can't be read, the routing table capture
+    /// is missing, or a capture is unparseable
+    pub fn from_directory(dir: impl AsRef<Path>) -> Result<Self, Error> {
+        let dir = dir.as_ref();
+        Self::from_captures(|name| {
+            let path = dir.join(name);
+            match std::fs::read(&path) {
+                Ok(contents) => Ok(Some(contents)),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(Error::Read { path, err }),
+            }
+        })
+    }
+
+    /// Load the captures from a sysdiagnose `.tar.gz` archive.  Captures are
+    /// found relative to the archive's top-level directory, which is named
+    /// after the host and time of the sysdiagnose.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the archive can't be read, the routing table
+    /// capture is missing, or a capture is unparseable
+    pub fn from_archive(reader: impl Read) -> Result<Self, Error> {
+        let known = [NETSTAT_CAPTURE, IFCONFIG_CAPTURE, SCUTIL_DNS_CAPTURE];
+        let mut captures = HashMap::new();
+        let mut archive = tar::Archive::new(GzDecoder::new(reader));
+        for entry in archive.entries().map_err(Error::Archive)? {
+            let mut entry = entry.map_err(Error::Archive)?;
+            let name = entry
+                .path()
+                .map_err(Error::Archive)?
+                .components()
+                .skip(1)
+                .collect::<PathBuf>();
+            let capture = known
+                .iter()
+                .copied()
+                .find(|capture| name == Path::new(capture));
+            if let Some(capture) = capture {
+                let mut contents = vec![];
+                entry.read_to_end(&mut contents).map_err(Error::Archive)?;
+                captures.insert(capture, contents);
+            }
+        }
+        Self::from_captures(|name| Ok(captures.remove(name)))
+    }
+
+    /// Build a snapshot from the captures returned by `read`, which returns
+    /// `None` for captures that don't exist
+    fn from_captures(
+        mut read: impl FnMut(&'static str) -> Result<Option<Vec<u8>>, Error>,
+    ) -> Result<Self, Error> {
+        let mut read_text = |file: &'static str| -> Result<Option<String>, Error> {
+            read(file)?
+                .map(|contents| String::from_utf8(contents).map_err(|_| Error::NotUtf8 { file }))
+                .transpose()
+        };
+
+        // The listing is one of several commands captured in the file
+        let text = read_text(NETSTAT_CAPTURE)?.ok_or(Error::MissingCapture {
+            capture: "routing table",
+            path: NETSTAT_CAPTURE,
+        })?;
+        let routing_table = RoutingTable::scan_text(&text)
+            .into_iter()
+            .next()
+            .ok_or(Error::NoRoutingTable {
+                file: NETSTAT_CAPTURE,
+            })?
+            .table;
+
+        let interfaces = read_text(IFCONFIG_CAPTURE)?
+            .map(|text| {
+                InterfaceTable::parse(&text).map_err(|err| Error::Interfaces {
+                    file: IFCONFIG_CAPTURE,
+                    err,
+                })
+            })
+            .transpose()?;
+        let dns = read_text(SCUTIL_DNS_CAPTURE)?
+            .map(|text| {
+                DnsConfiguration::parse(&text).map_err(|err| Error::Dns {
+                    file: SCUTIL_DNS_CAPTURE,
+                    err,
+                })
+            })
+            .transpose()?;
+
+        let mut missing = vec![];
+        if interfaces.is_none() {
+            missing.push(MissingCapture {
+                capture: "ifconfig",
+                path: IFCONFIG_CAPTURE,
+            });
+        }
+        if dns.is_none() {
+            missing.push(MissingCapture {
+                capture: "scutil --dns",
+                path: SCUTIL_DNS_CAPTURE,
+            });
+        }
+
+        Ok(SysdiagnoseSnapshot {
+            routing_table,
+            interfaces,
+            dns,
+            missing,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, SysdiagnoseSnapshot};
+    use flate2::{write::GzEncoder, Compression};
+
+    include!(concat!(env!("OUT_DIR"), "/sample_table.rs"));
+
+    const TOP: &str = "sysdiagnose_2026.10.16_13-02-11-0700_macOS_MacBookPro18-3_23G93";
+
+    /// Build a sysdiagnose archive holding `files`
+    fn build_archive(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(vec![], Compression::default()));
+        for (name, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, format!("{TOP}/{name}"), contents.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn load_archive() {
+        let netstat = format!("$ netstat -rn\n{SAMPLE_TABLE}\n$ netstat -s\ntcp:\n");
+        let archive = build_archive(&[
+            ("network-info/netstat.txt", &netstat),
+            ("network-info/ifconfig.txt", IFCONFIG_AV),
+            ("network-info/dns-configuration.txt", SCUTIL_DNS),
+            ("logs/unrelated.log", "nothing to see"),
+        ]);
+        let snapshot = SysdiagnoseSnapshot::from_archive(archive.as_slice()).unwrap();
+        let entry = snapshot
+            .routing_table
+            .find_route_entry("1.1.1.1".parse().unwrap())
+            .unwrap();
+        assert_eq!(entry.net_if, "en0");
+        let interfaces = snapshot.interfaces.expect("interfaces");
+        assert_eq!(interfaces.by_index(5).unwrap().name, "en0");
+        assert!(!snapshot.dns.expect("resolvers").resolvers.is_empty());
+        assert!(snapshot.missing.is_empty());
+    }
+
+    #[test]
+    fn load_directory() {
+        let dir = std::env::temp_dir().join(format!("{TOP}-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("network-info")).unwrap();
+        std::fs::write(dir.join("network-info/netstat.txt"), SAMPLE_TABLE).unwrap();
+        let snapshot = SysdiagnoseSnapshot::open(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let snapshot = snapshot.unwrap();
+        assert!(!snapshot.routing_table.routes().is_empty());
+        assert!(snapshot.interfaces.is_none());
+        assert!(snapshot.dns.is_none());
+        assert_eq!(
+            snapshot
+                .missing
+                .iter()
+                .map(|missing| missing.capture)
+                .collect::<Vec<_>>(),
+            ["ifconfig", "scutil --dns"]
+        );
+        assert_eq!(
+            snapshot.missing[0].to_string(),
+            "no ifconfig capture (looked for network-info/ifconfig.txt)"
+        );
+    }
+
+    #[test]
+    fn missing_captures() {
+        let archive = build_archive(&[("network-info/ifconfig.txt", IFCONFIG_AV)]);
+        let err = SysdiagnoseSnapshot::from_archive(archive.as_slice()).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::MissingCapture {
+                capture: "routing table",
+                ..
+            }
+        ));
+        assert!(err.to_string().contains("network-info/netstat.txt"));
+
+        // Captures at the top level aren't where sysdiagnose puts them
+        let archive = build_archive(&[("netstat.txt", SAMPLE_TABLE)]);
+        assert!(matches!(
+            SysdiagnoseSnapshot::from_archive(archive.as_slice()),
+            Err(Error::MissingCapture { .. })
+        ));
+
+        let archive =
+            build_archive(&[("network-info/netstat.txt", "netstat: sysctl: No such file")]);
+        assert!(matches!(
+            SysdiagnoseSnapshot::from_archive(archive.as_slice()),
+            Err(Error::NoRoutingTable {
+                file: "network-info/netstat.txt"
+            })
+        ));
+
+        let archive = build_archive(&[
+            ("network-info/netstat.txt", SAMPLE_TABLE),
+            ("network-info/ifconfig.txt", "\tinet 127.0.0.1\n"),
+        ]);
+        assert!(matches!(
+            SysdiagnoseSnapshot::from_archive(archive.as_slice()),
+            Err(Error::Interfaces {
+                file: "network-info/ifconfig.txt",
+                ..
+            })
+        ));
+
+        assert!(matches!(
+            SysdiagnoseSnapshot::open("/nonexistent/sysdiagnose.tar.gz"),
+            Err(Error::Read { .. })
+        ));
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{routing_table::Error, LookupOptions, NetstatOptions, RouteEntry, RoutingTable};
+use std::{collections::BTreeMap, iter::FromIterator, net::IpAddr};
+
+/// The table ID of tables that don't name one
+const DEFAULT_TABLE_ID: u32 = 0;
+
+/// The routing tables of several FIBs (FreeBSD) or routing domains (OpenBSD),
+/// keyed by table ID.  A table without an ID is the default table, 0.
+#[derive(Debug, Default)]
+pub struct RoutingTableSet {
+    tables: BTreeMap<u32, RoutingTable>,
+}
+
+impl RoutingTableSet {
+    /// Query the routing table of each of `table_ids` using the `netstat`
+    /// command, invoked according to `options`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `netstat` command fails to execute, or returns
+    /// unparseable output.
+    pub async fn load_from_netstat_with(
+        table_ids: impl IntoIterator<Item = u32>,
+        options: &NetstatOptions,
+    ) -> Result<Self, Error> {
+        let mut set = RoutingTableSet::default();
+        for table_id in table_ids {
+            let options = NetstatOptions {
+                fib: Some(table_id),
+                ..options.clone()
+            };
+            set.insert(RoutingTable::load_from_netstat_with(&options).await?);
+        }
+        Ok(set)
+    }
+
+    /// Add a table, returning the one it replaces with the same table ID
+    pub fn insert(&mut self, table: RoutingTable) -> Option<RoutingTable> {
+        self.tables
+            .insert(table.table_id().unwrap_or(DEFAULT_TABLE_ID), table)
+    }
+
+    /// The table with the given ID
+    #[must_use]
+    pub fn get(&self, table_id: u32) -> Option<&RoutingTable> {
+        self.tables.get(&table_id)
+    }
+
+    /// All tables, in table ID order
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &RoutingTable)> {
+        self.tables
+            .iter()
+            .map(|(&table_id, table)| (table_id, table))
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Find the entry in table `table_id` that most-precisely matches the
+    /// provided address.  Returns `None` if there's no such table.
+    #[must_use]
+    pub fn find_route_entry(&self, table_id: u32, addr: IpAddr) -> Option<&RouteEntry> {
+        self.get(table_id)?.find_route_entry(addr)
+    }
+
+    /// Find the entry in table `table_id` that most-precisely matches the
+    /// provided address, breaking ties according to `options`.
+    #[must_use]
+    pub fn find_route_entry_with(
+        &self,
+        table_id: u32,
+        addr: IpAddr,
+        options: &LookupOptions,
+    ) -> Option<&RouteEntry> {
+        self.get(table_id)?.find_route_entry_with(addr, options)
+    }
+}
+
+impl FromIterator<RoutingTable> for RoutingTableSet {
+    fn from_iter<I: IntoIterator<Item = RoutingTable>>(tables: I) -> Self {
+        let mut set = RoutingTableSet::default();
+        for table in tables {
+            set.insert(table);
+        }
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RoutingTableSet;
+    use crate::RoutingTable;
+
+    include!(concat!(env!("OUT_DIR"), "/sample_table.rs"));
+
+    #[test]
+    fn lookups_by_table() {
+        let default = RoutingTable::from_netstat_output(SAMPLE_TABLE).unwrap();
+        let fib1 = RoutingTable::from_netstat_output(SAMPLE_TABLE_FIB1).unwrap();
+        assert_eq!(default.table_id(), None);
+        assert_eq!(fib1.table_id(), Some(1));
+
+        let set: RoutingTableSet = vec![default, fib1].into_iter().collect();
+        assert_eq!(set.iter().map(|(id, _)| id).collect::<Vec<_>>(), [0, 1]);
+        let addr = "1.1.1.1".parse().unwrap();
+        assert_eq!(set.find_route_entry(0, addr).unwrap().net_if, "en0");
+        assert_eq!(set.find_route_entry(1, addr).unwrap().net_if, "utun3");
+        assert!(set.find_route_entry(2, addr).is_none());
+
+        // The title carries the FIB through embedded listings, too
+        let tables = RoutingTable::scan_text(SAMPLE_TABLE_FIB1);
+        assert_eq!(tables[0].table.table_id(), Some(1));
+    }
+
+    #[test]
+    fn insert_replaces() {
+        let mut set = RoutingTableSet::default();
+        assert!(set.is_empty());
+        let table = RoutingTable::from_netstat_output(SAMPLE_TABLE_FIB1).unwrap();
+        assert!(set.insert(table).is_none());
+        let table = RoutingTable::from_routes(vec![]).with_table_id(1);
+        let replaced = set.insert(table).unwrap();
+        assert!(!replaced.routes().is_empty());
+        assert_eq!(set.len(), 1);
+        assert!(set.get(1).unwrap().routes().is_empty());
+    }
+}
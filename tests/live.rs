@@ -1,3 +1,4 @@
+#![cfg(target_os = "macos")]
 #![allow(clippy::missing_panics_doc, clippy::missing_errors_doc)]
 
 use anyhow::Result;
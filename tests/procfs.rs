@@ -0,0 +1,14 @@
+#![cfg(target_os = "linux")]
+#![allow(clippy::missing_panics_doc, clippy::missing_errors_doc)]
+
+use anyhow::Result;
+use macos_routing_table::RoutingTable;
+
+#[tokio::test]
+pub async fn main() -> Result<()> {
+    let table = RoutingTable::load_from_procfs().await?;
+    eprintln!("{table:?}");
+    assert!(table.routes().iter().all(|route| !route.net_if.is_empty()));
+
+    Ok(())
+}